@@ -0,0 +1,406 @@
+use std::{
+    collections::BTreeSet,
+    io::{self, BufRead, Write},
+    path::Path,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+use serde_json::{json, Value as Json};
+
+use crate::{
+    debugger::{Debugger, Event, Pause, Resume},
+    dialect::Dialect,
+    interpreter::{CancelToken, Interpreter, Value},
+    parser::{self, Expr},
+    protocol,
+};
+
+/// BASIC programs run on a single thread, which is all the adapter reports.
+const THREAD_ID: i64 = 1;
+/// Every variable is global, so there is only one scope to expand.
+const GLOBALS: i64 = 1;
+
+/// Serves the Debug Adapter Protocol for one debug session, reading
+/// requests from `input` and writing responses and events to `output`.
+///
+/// The program's `INPUT` statements are answered by expressions typed into
+/// the editor's debug console while the program is waiting.
+pub fn serve<R, W>(input: R, output: W, dialect: Dialect) -> io::Result<()>
+where
+    R: BufRead + Send + 'static,
+    W: Write,
+{
+    let interrupt: Arc<Mutex<Interrupt>> = Arc::default();
+    let requests = read_requests(input, interrupt.clone());
+    let mut connection = Connection { output, seq: 1 };
+
+    let mut breakpoints = Vec::new();
+    // Clients may finish configuring before they launch, in which case the
+    // program starts as soon as it is loaded.
+    let mut configured = false;
+    let (path, exprs, stop_on_entry) = loop {
+        let Ok(request) = requests.recv() else {
+            return Ok(());
+        };
+
+        match request["command"].as_str().unwrap_or_default() {
+            "initialize" => {
+                connection.respond(&request, json!({
+                    "supportsConfigurationDoneRequest": true,
+                    "supportsEvaluateForHovers": true,
+                }))?;
+                connection.event("initialized", json!({}))?;
+            }
+            "setBreakpoints" => {
+                breakpoints = requested_lines(&request);
+                let verified: Vec<_> = breakpoints.iter().map(|line| json!({ "verified": true, "line": line })).collect();
+                connection.respond(&request, json!({ "breakpoints": verified }))?;
+            }
+            "launch" => match load(&request, dialect) {
+                Ok(program) => {
+                    connection.respond(&request, json!({}))?;
+                    break program;
+                }
+                Err(message) => connection.fail(&request, &message)?,
+            },
+            "configurationDone" => {
+                configured = true;
+                connection.respond(&request, json!({}))?;
+            }
+            "disconnect" | "terminate" => return connection.respond(&request, json!({})),
+            _ => connection.respond(&request, json!({}))?,
+        }
+    };
+
+    let mut lines = BTreeSet::new();
+    statement_lines(&exprs, &mut lines);
+
+    let mut interpreter = Interpreter::with_dialect(&exprs, dialect);
+    let token = interpreter.cancel_token();
+
+    let mut session = Session {
+        connection,
+        debugger: Debugger::new(&mut interpreter),
+        path,
+        lines,
+        state: State::Paused,
+    };
+    for line in breakpoints {
+        session.debugger.set_breakpoint(line);
+    }
+    interrupt.lock().expect("interrupt lock poisoned").install(token);
+    if configured {
+        session.start(stop_on_entry)?;
+    }
+
+    for request in requests {
+        match request["command"].as_str().unwrap_or_default() {
+            "configurationDone" => {
+                session.connection.respond(&request, json!({}))?;
+                session.start(stop_on_entry)?;
+            }
+            "continue" => session.resume(&request, Resume::Continue, json!({ "allThreadsContinued": true }))?,
+            "next" => session.resume(&request, Resume::StepOver, json!({}))?,
+            "stepIn" => session.resume(&request, Resume::StepInto, json!({}))?,
+            "stepOut" => session.resume(&request, Resume::StepOut, json!({}))?,
+            "disconnect" | "terminate" => return session.connection.respond(&request, json!({})),
+            _ => session.answer(&request)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// How the request reader breaks into a running program.
+#[derive(Default)]
+struct Interrupt {
+    /// The program's cancel token, once it is loaded.
+    token: Option<CancelToken>,
+    /// A `disconnect` or `terminate` arrived, so the program must not run
+    /// on even if it only starts later.
+    ending: bool,
+}
+
+impl Interrupt {
+    fn install(&mut self, token: CancelToken) {
+        if self.ending {
+            token.cancel();
+        }
+        self.token = Some(token);
+    }
+
+    fn cancel(&self) {
+        if let Some(token) = &self.token {
+            token.cancel();
+        }
+    }
+}
+
+/// Reads requests on a separate thread so that `pause`, `disconnect` and
+/// `terminate` requests can break into a running program through its
+/// cancel token. The session handles them once the program has stopped.
+fn read_requests<R>(mut input: R, interrupt: Arc<Mutex<Interrupt>>) -> mpsc::Receiver<Json>
+where
+    R: BufRead + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        while let Ok(Some(request)) = protocol::read_message(&mut input) {
+            let command = request["command"].as_str().unwrap_or_default();
+            if matches!(command, "pause" | "disconnect" | "terminate") {
+                let mut interrupt = interrupt.lock().expect("interrupt lock poisoned");
+                interrupt.ending |= command != "pause";
+                interrupt.cancel();
+            }
+            if sender.send(request).is_err() {
+                break;
+            }
+        }
+    });
+
+    receiver
+}
+
+/// Reads and parses the program named by a `launch` request, returning its
+/// path, its statements and whether to stop on entry.
+fn load(launch: &Json, dialect: Dialect) -> Result<(String, Vec<Expr>, bool), String> {
+    let path = launch["arguments"]["program"].as_str().unwrap_or_default().to_string();
+    let stop_on_entry = launch["arguments"]["stopOnEntry"].as_bool().unwrap_or(false);
+
+    let source = std::fs::read_to_string(&path).map_err(|error| format!("{}: {}", path, error))?;
+    let (exprs, errors) = parser::parse_program(&source, dialect);
+    if !errors.is_empty() {
+        let errors: Vec<_> = errors.iter().map(ToString::to_string).collect();
+        return Err(errors.join("\n"));
+    }
+    Ok((path, exprs, stop_on_entry))
+}
+
+/// Collects the source lines that start statements, where breakpoints can
+/// be hit.
+fn statement_lines(exprs: &[Expr], lines: &mut BTreeSet<usize>) {
+    for expr in exprs {
+        match expr {
+            Expr::Line(line) => {
+                lines.insert(*line);
+            }
+            Expr::For { body, .. } => statement_lines(body, lines),
+            Expr::Select { cases, default, .. } => {
+                for case in cases {
+                    statement_lines(&case.body, lines);
+                }
+                if let Some(default) = default {
+                    statement_lines(default, lines);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn requested_lines(request: &Json) -> Vec<usize> {
+    request["arguments"]["breakpoints"].as_array().into_iter().flatten()
+        .filter_map(|breakpoint| breakpoint["line"].as_u64())
+        .map(|line| line as usize)
+        .collect()
+}
+
+fn describe(value: &Value) -> (String, &'static str) {
+    match value {
+        Value::Integer(n) => (n.to_string(), "Integer"),
+        Value::Single(f) => (f.to_string(), "Single"),
+        Value::Float(f) => (f.to_string(), "Float"),
+        Value::String(s) => (format!("{:?}", s), "String"),
+        Value::Bool(b) => (b.to_string(), "Boolean"),
+    }
+}
+
+struct Connection<W: Write> {
+    output: W,
+    seq: i64,
+}
+
+impl<W: Write> Connection<W> {
+    fn send(&mut self, mut message: Json) -> io::Result<()> {
+        message["seq"] = json!(self.seq);
+        self.seq += 1;
+        protocol::write_message(&mut self.output, &message)
+    }
+
+    fn respond(&mut self, request: &Json, body: Json) -> io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "success": true,
+            "command": request["command"],
+            "body": body,
+        }))
+    }
+
+    fn fail(&mut self, request: &Json, message: &str) -> io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "success": false,
+            "command": request["command"],
+            "message": message,
+        }))
+    }
+
+    fn event(&mut self, event: &str, body: Json) -> io::Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Paused,
+    AwaitingInput,
+    Ended,
+}
+
+struct Session<'i, 'a, W: Write> {
+    connection: Connection<W>,
+    debugger: Debugger<'i, 'a>,
+    path: String,
+    /// Lines that start statements, for verifying breakpoints.
+    lines: BTreeSet<usize>,
+    state: State,
+}
+
+impl<W: Write> Session<'_, '_, W> {
+    /// Runs the program until it pauses, waits for input or ends,
+    /// forwarding its output as events.
+    fn drive(&mut self) -> io::Result<()> {
+        loop {
+            match self.debugger.next_event() {
+                Ok(Event::Output(text)) => {
+                    self.connection.event("output", json!({ "category": "stdout", "output": text }))?;
+                }
+                Ok(Event::NeedsInput(prompt)) => {
+                    self.connection.event("output", json!({ "category": "console", "output": format!("{}\n", prompt) }))?;
+                    self.state = State::AwaitingInput;
+                    return Ok(());
+                }
+                Ok(Event::Paused(pause)) => {
+                    let reason = match pause {
+                        Pause::Entry(_) => "entry",
+                        Pause::Breakpoint(_) => "breakpoint",
+                        Pause::Step(_) => "step",
+                        Pause::Break(_) => "pause",
+                    };
+                    self.connection.event("stopped", json!({
+                        "reason": reason,
+                        "threadId": THREAD_ID,
+                        "allThreadsStopped": true,
+                    }))?;
+                    self.state = State::Paused;
+                    return Ok(());
+                }
+                Ok(Event::Finished) => return self.end(0),
+                Err(error) => {
+                    self.connection.event("output", json!({ "category": "stderr", "output": format!("{}\n", error) }))?;
+                    return self.end(1);
+                }
+            }
+        }
+    }
+
+    /// Runs the program from its entry, or pauses there.
+    fn start(&mut self, stop_on_entry: bool) -> io::Result<()> {
+        if !stop_on_entry {
+            self.debugger.resume(Resume::Continue);
+        }
+        self.drive()
+    }
+
+    fn end(&mut self, exit_code: i64) -> io::Result<()> {
+        self.state = State::Ended;
+        self.connection.event("exited", json!({ "exitCode": exit_code }))?;
+        self.connection.event("terminated", json!({}))
+    }
+
+    fn resume(&mut self, request: &Json, resume: Resume, body: Json) -> io::Result<()> {
+        if self.state != State::Paused {
+            return self.connection.fail(request, "The program is not paused");
+        }
+
+        self.connection.respond(request, body)?;
+        self.debugger.resume(resume);
+        self.drive()
+    }
+
+    /// Answers requests that inspect the paused program.
+    fn answer(&mut self, request: &Json) -> io::Result<()> {
+        let arguments = &request["arguments"];
+
+        match request["command"].as_str().unwrap_or_default() {
+            "setBreakpoints" => {
+                self.debugger.clear_breakpoints();
+
+                let mut verified = Vec::new();
+                for line in requested_lines(request) {
+                    self.debugger.set_breakpoint(line);
+                    verified.push(json!({ "verified": self.lines.contains(&line), "line": line }));
+                }
+                self.connection.respond(request, json!({ "breakpoints": verified }))
+            }
+            "threads" => {
+                self.connection.respond(request, json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] }))
+            }
+            "stackTrace" => {
+                let name = Path::new(&self.path).file_name().map(|name| name.to_string_lossy().into_owned());
+                let frames: Vec<_> = self.debugger.call_stack().iter().enumerate()
+                    .map(|(id, frame)| json!({
+                        "id": id,
+                        "name": frame.name,
+                        "line": frame.line,
+                        "column": 1,
+                        "source": { "name": name, "path": self.path },
+                    }))
+                    .collect();
+                self.connection.respond(request, json!({ "totalFrames": frames.len(), "stackFrames": frames }))
+            }
+            "scopes" => {
+                self.connection.respond(request, json!({
+                    "scopes": [{ "name": "Globals", "variablesReference": GLOBALS, "expensive": false }],
+                }))
+            }
+            "variables" => {
+                let variables: Vec<_> = match arguments["variablesReference"].as_i64() {
+                    Some(GLOBALS) => self.debugger.variables().iter()
+                        .map(|(name, value)| {
+                            let (value, kind) = describe(value);
+                            json!({ "name": name, "value": value, "type": kind, "variablesReference": 0 })
+                        })
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                self.connection.respond(request, json!({ "variables": variables }))
+            }
+            "evaluate" => {
+                let expression = arguments["expression"].as_str().unwrap_or_default();
+
+                if self.state == State::AwaitingInput && arguments["context"] == "repl" {
+                    self.debugger.provide_input(expression);
+                    self.connection.respond(request, json!({ "result": "", "variablesReference": 0 }))?;
+                    self.state = State::Paused;
+                    return self.drive();
+                }
+
+                match self.debugger.evaluate(expression) {
+                    Ok(Ok(value)) => {
+                        let (result, kind) = describe(&value);
+                        self.connection.respond(request, json!({ "result": result, "type": kind, "variablesReference": 0 }))
+                    }
+                    Ok(Err(error)) => self.connection.fail(request, &error.to_string()),
+                    Err(error) => self.connection.fail(request, &error.to_string()),
+                }
+            }
+            "pause" => self.connection.respond(request, json!({})),
+            command => self.connection.fail(request, &format!("Unsupported request {}", command)),
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use std::{collections::BTreeSet, io};
+
+use crate::{
+    error::{ErrorKind, RuntimeError, SyntaxError},
+    interpreter::{Completion, Interpreter, StackFrame, StepResult, Value},
+    lexer::Lexer,
+    parser::{Expr, Parser},
+};
+
+/// Why the debugger paused, with the line about to run.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pause {
+    /// Before the first line of the program.
+    Entry(usize),
+    Breakpoint(usize),
+    /// A step finished.
+    Step(usize),
+    /// The program executed `STOP` or was cancelled on this line.
+    Break(usize),
+}
+
+impl Pause {
+    pub fn line(&self) -> usize {
+        match self {
+            Pause::Entry(line) | Pause::Breakpoint(line) | Pause::Step(line) | Pause::Break(line) => *line,
+        }
+    }
+}
+
+/// How to go on after a pause. Steps move by source line; a block such as
+/// a `FOR` body counts as one level deeper than the line that opened it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Resume {
+    /// Run until a breakpoint or the end of the program.
+    Continue,
+    /// Pause at the next line, including lines inside blocks.
+    StepInto,
+    /// Pause at the next line that is not inside a deeper block.
+    StepOver,
+    /// Pause at the first line after the current block is left.
+    StepOut,
+}
+
+/// What `Debugger::next_event` stopped for.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    Paused(Pause),
+    Output(String),
+    NeedsInput(String),
+    Finished,
+}
+
+/// Receives pauses, output and input requests from `Debugger::run`.
+pub trait DebugHook {
+    /// Inspect the paused program through `debugger` and choose how to go on.
+    fn paused(&mut self, debugger: &mut Debugger, pause: Pause) -> Resume;
+
+    fn output(&mut self, text: &str) {
+        print!("{}", text);
+    }
+
+    fn input(&mut self, prompt: &str) -> String {
+        println!("{}", prompt);
+
+        let mut line = String::new();
+        io::stdin().read_line(&mut line).expect("Failed to read line");
+        line.trim_end_matches(['\r', '\n']).to_string()
+    }
+}
+
+/// Runs a program under control of breakpoints and steps. The program is
+/// started from the beginning and pauses before its first line.
+pub struct Debugger<'i, 'a> {
+    interpreter: &'i mut Interpreter<'a>,
+    breakpoints: BTreeSet<usize>,
+    watches: Vec<(String, Expr)>,
+    resume: Resume,
+    /// Depth of the block the last pause happened in.
+    depth: usize,
+    at_entry: bool,
+    /// The next line is the one just paused at, so it must not pause again.
+    paused_at_line: bool,
+}
+
+/// Parses a watch or an expression to evaluate.
+fn parse_expression(source: &str) -> Result<Expr, SyntaxError> {
+    let mut lexer = Lexer::new(source.to_string());
+    let tokens = lexer.scan();
+    if let Some(error) = lexer.errors().first() {
+        return Err(error.clone());
+    }
+
+    Parser::with_spans(&tokens, lexer.spans()).parse_expression()
+}
+
+impl<'i, 'a> Debugger<'i, 'a> {
+    pub fn new(interpreter: &'i mut Interpreter<'a>) -> Debugger<'i, 'a> {
+        interpreter.begin();
+
+        Debugger {
+            interpreter,
+            breakpoints: BTreeSet::new(),
+            watches: Vec::new(),
+            resume: Resume::StepInto,
+            depth: 0,
+            at_entry: true,
+            paused_at_line: false,
+        }
+    }
+
+    pub fn interpreter(&mut self) -> &mut Interpreter<'a> {
+        self.interpreter
+    }
+
+    pub fn set_breakpoint(&mut self, line: usize) {
+        self.breakpoints.insert(line);
+    }
+
+    pub fn clear_breakpoint(&mut self, line: usize) -> bool {
+        self.breakpoints.remove(&line)
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    pub fn add_watch(&mut self, source: &str) -> Result<(), SyntaxError> {
+        self.watches.push((source.to_string(), parse_expression(source)?));
+        Ok(())
+    }
+
+    pub fn remove_watch(&mut self, index: usize) -> bool {
+        if index >= self.watches.len() {
+            return false;
+        }
+        self.watches.remove(index);
+        true
+    }
+
+    /// Every watch with its current value, in the order they were added.
+    pub fn watches(&mut self) -> Vec<(String, Result<Value, ErrorKind>)> {
+        let mut values = Vec::new();
+        for (source, expr) in &self.watches {
+            values.push((source.clone(), self.interpreter.evaluate(expr)));
+        }
+        values
+    }
+
+    /// Evaluates an expression against the paused program. The outer error
+    /// is a mistake in `source`, the inner one an error evaluating it.
+    pub fn evaluate(&mut self, source: &str) -> Result<Result<Value, ErrorKind>, SyntaxError> {
+        Ok(self.interpreter.evaluate(&parse_expression(source)?))
+    }
+
+    /// The program's variables, sorted by name.
+    pub fn variables(&self) -> Vec<(String, Value)> {
+        let mut variables: Vec<_> = self.interpreter.vars()
+            .map(|(name, val)| (name.to_string(), val.clone()))
+            .collect();
+        variables.sort_by(|a, b| a.0.cmp(&b.0));
+        variables
+    }
+
+    pub fn call_stack(&self) -> Vec<StackFrame> {
+        self.interpreter.call_stack()
+    }
+
+    pub fn resume(&mut self, resume: Resume) {
+        self.resume = resume;
+        self.depth = self.interpreter.depth();
+        self.at_entry = false;
+    }
+
+    pub fn provide_input(&mut self, line: impl Into<String>) {
+        self.interpreter.provide_input(line);
+    }
+
+    fn should_pause(&self, line: usize) -> Option<Pause> {
+        let depth = self.interpreter.depth();
+
+        if self.at_entry {
+            Some(Pause::Entry(line))
+        } else if self.breakpoints.contains(&line) {
+            Some(Pause::Breakpoint(line))
+        } else {
+            let step = match self.resume {
+                Resume::Continue => false,
+                Resume::StepInto => true,
+                Resume::StepOver => depth <= self.depth,
+                Resume::StepOut => depth < self.depth,
+            };
+            step.then_some(Pause::Step(line))
+        }
+    }
+
+    /// Runs until the program pauses, prints, asks for input or finishes.
+    pub fn next_event(&mut self) -> Result<Event, RuntimeError> {
+        loop {
+            if let Some(text) = self.interpreter.take_output() {
+                return Ok(Event::Output(text));
+            }
+
+            if !std::mem::take(&mut self.paused_at_line) {
+                if let Some(pause) = self.interpreter.next_line().and_then(|line| self.should_pause(line)) {
+                    self.paused_at_line = true;
+                    return Ok(Event::Paused(pause));
+                }
+            }
+
+            match self.interpreter.step()? {
+                StepResult::Running => {}
+                StepResult::Output(text) => return Ok(Event::Output(text)),
+                StepResult::NeedsInput(prompt) => return Ok(Event::NeedsInput(prompt)),
+                StepResult::Finished => return Ok(Event::Finished),
+                StepResult::Break(line) => {
+                    self.interpreter.begin_cont()?;
+                    return Ok(Event::Paused(Pause::Break(line)));
+                }
+            }
+        }
+    }
+
+    /// Runs the program to the end, handing every event to `hook`.
+    pub fn run(&mut self, hook: &mut impl DebugHook) -> Result<Completion, RuntimeError> {
+        loop {
+            match self.next_event()? {
+                Event::Paused(pause) => {
+                    let resume = hook.paused(self, pause);
+                    self.resume(resume);
+                }
+                Event::Output(text) => hook.output(&text),
+                Event::NeedsInput(prompt) => {
+                    let line = hook.input(&prompt);
+                    self.provide_input(line);
+                }
+                Event::Finished => return Ok(Completion::Finished),
+            }
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use serde_json::json;
+
+use crate::{
+    error::{RuntimeError, SyntaxError},
+    lexer::Span,
+};
+
+/// How diagnostics are printed.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Format {
+    /// For people: the source line with the mistake underlined.
+    #[default]
+    Plain,
+    /// One JSON object per line, for editors and CI.
+    Json,
+}
+
+/// An error that can be shown against the source it was found in.
+pub trait Diagnostic {
+    fn message(&self) -> String;
+    /// Where the error is. A column of 0 stands for the whole line.
+    fn span(&self) -> Span;
+    fn help(&self) -> Option<&str>;
+}
+
+impl Diagnostic for SyntaxError {
+    fn message(&self) -> String {
+        self.message.clone()
+    }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn help(&self) -> Option<&str> {
+        self.help.as_deref()
+    }
+}
+
+impl Diagnostic for RuntimeError {
+    fn message(&self) -> String {
+        self.kind.to_string()
+    }
+
+    fn span(&self) -> Span {
+        Span { line: self.line, col: 0, len: 0 }
+    }
+
+    fn help(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// Renders an error found in the file at `path`, whose contents are
+/// `source`.
+///
+/// ```text
+/// error: Expected `THEN`, got `THN`
+///  --> example.bas:3:10
+///   |
+/// 3 | IF A > 1 THN PRINT A
+///   |          ^^^
+///   = help: did you mean `THEN`?
+/// ```
+pub fn render(error: &impl Diagnostic, path: &str, source: &str, format: Format) -> String {
+    match format {
+        Format::Plain => plain(error, path, source),
+        Format::Json => json(error, path),
+    }
+}
+
+fn plain(error: &impl Diagnostic, path: &str, source: &str) -> String {
+    let span = error.span();
+    let mut out = format!("error: {}\n", error.message());
+
+    let Some(line) = source.lines().nth(span.line.wrapping_sub(1)) else {
+        out.push_str(&format!(" --> {}:{}", path, span.line));
+        return with_help(out, error);
+    };
+
+    let gutter = " ".repeat(span.line.to_string().len());
+    if span.col == 0 {
+        // The error is about the whole line, so there is nothing to point at.
+        out.push_str(&format!("{}--> {}:{}\n", gutter, path, span.line));
+        out.push_str(&format!("{} |\n", gutter));
+        out.push_str(&format!("{} | {}", span.line, line));
+        return with_help(out, error);
+    }
+
+    out.push_str(&format!("{}--> {}:{}:{}\n", gutter, path, span.line, span.col));
+    out.push_str(&format!("{} |\n", gutter));
+    out.push_str(&format!("{} | {}\n", span.line, line));
+
+    // Tabs are kept so the carets line up however the terminal shows them.
+    let indent: String = line.chars().take(span.col - 1).map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+    out.push_str(&format!("{} | {}{}", gutter, indent, "^".repeat(span.len.max(1))));
+
+    with_help(out, error)
+}
+
+fn with_help(mut out: String, error: &impl Diagnostic) -> String {
+    if let Some(help) = error.help() {
+        let gutter = " ".repeat(error.span().line.to_string().len());
+        out.push_str(&format!("\n{} = help: {}", gutter, help));
+    }
+    out
+}
+
+fn json(error: &impl Diagnostic, path: &str) -> String {
+    let span = error.span();
+    json!({
+        "severity": "error",
+        "file": path,
+        "line": span.line,
+        "column": span.col,
+        "end_column": span.col + span.len,
+        "message": error.message(),
+        "help": error.help(),
+    })
+    .to_string()
+}
+
+/// The candidate closest to `word`, ignoring case, if it is near enough to
+/// be a likely misspelling.
+pub(crate) fn similar<'c>(word: &str, candidates: &[&'c str]) -> Option<&'c str> {
+    let word = word.to_uppercase();
+    let max = match word.chars().count() {
+        0..=2 => 0,
+        3..=5 => 1,
+        _ => 2,
+    };
+
+    candidates.iter()
+        .map(|&candidate| (edit_distance(&word, candidate), candidate))
+        .filter(|&(distance, _)| distance <= max)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// The number of single-character insertions, deletions, substitutions
+/// and swaps of neighbouring characters that turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    d[a.len()][b.len()]
+}
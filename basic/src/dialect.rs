@@ -0,0 +1,31 @@
+/// Language options that differ between BASIC dialects.
+///
+/// The default matches the behaviour of the interpreter before dialects
+/// were introduced.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Dialect {
+    /// Recognise `\n`, `\t`, `\\` and `\"` escapes inside string literals.
+    pub string_escapes: bool,
+    /// What happens when integer arithmetic leaves the 64-bit range.
+    pub overflow: Overflow,
+    /// How comparisons are represented and which values `IF` accepts.
+    pub truth: Truth,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Overflow {
+    /// Raise an "Overflow" error.
+    #[default]
+    Error,
+    /// Redo the operation in floating point.
+    Promote,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Truth {
+    /// Comparisons yield booleans and `IF` only accepts booleans.
+    #[default]
+    Boolean,
+    /// Comparisons yield -1 or 0 and `IF` treats any non-zero number as true.
+    Classic,
+}
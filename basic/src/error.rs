@@ -0,0 +1,164 @@
+use std::fmt;
+
+use crate::{lexer::Span, limits::Limit};
+
+/// A BASIC runtime error. Codes follow QBasic wherever it has an equivalent
+/// error; errors specific to this interpreter are numbered from 100.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ErrorKind {
+    /// The program reached a statement that failed to parse.
+    Syntax(String),
+    OutOfData,
+    IllegalFunctionCall(String),
+    Overflow,
+    /// A jump to a line that does not exist, or 0 when raised by `ERROR 8`.
+    LabelNotDefined(usize),
+    DivisionByZero,
+    TypeMismatch(String),
+    CantContinue,
+    NoResume,
+    ResumeWithoutError,
+    FieldOverflow,
+    BadFileNumber,
+    FileNotFound(String),
+    BadFileMode,
+    FileAlreadyOpen,
+    DeviceIo(String),
+    InputPastEnd,
+    BadRecordNumber,
+    PermissionDenied(String),
+    UndefinedVariable(String),
+    /// The program ran into one of the host's `Limits`. `ON ERROR` cannot
+    /// trap it.
+    LimitExceeded(Limit),
+    /// Raised by an `ERROR n` statement with a code that has no variant.
+    Raised(i64),
+}
+
+impl ErrorKind {
+    pub fn code(&self) -> i64 {
+        match self {
+            ErrorKind::Syntax(_) => 2,
+            ErrorKind::OutOfData => 4,
+            ErrorKind::IllegalFunctionCall(_) => 5,
+            ErrorKind::Overflow => 6,
+            ErrorKind::LabelNotDefined(_) => 8,
+            ErrorKind::DivisionByZero => 11,
+            ErrorKind::TypeMismatch(_) => 13,
+            ErrorKind::CantContinue => 17,
+            ErrorKind::NoResume => 19,
+            ErrorKind::ResumeWithoutError => 20,
+            ErrorKind::FieldOverflow => 50,
+            ErrorKind::BadFileNumber => 52,
+            ErrorKind::FileNotFound(_) => 53,
+            ErrorKind::BadFileMode => 54,
+            ErrorKind::FileAlreadyOpen => 55,
+            ErrorKind::DeviceIo(_) => 57,
+            ErrorKind::InputPastEnd => 62,
+            ErrorKind::BadRecordNumber => 63,
+            ErrorKind::PermissionDenied(_) => 70,
+            ErrorKind::UndefinedVariable(_) => 100,
+            ErrorKind::LimitExceeded(_) => 101,
+            ErrorKind::Raised(code) => *code,
+        }
+    }
+
+    pub fn from_code(code: i64) -> ErrorKind {
+        match code {
+            2 => ErrorKind::Syntax(String::new()),
+            4 => ErrorKind::OutOfData,
+            5 => ErrorKind::IllegalFunctionCall(String::new()),
+            6 => ErrorKind::Overflow,
+            8 => ErrorKind::LabelNotDefined(0),
+            11 => ErrorKind::DivisionByZero,
+            13 => ErrorKind::TypeMismatch(String::new()),
+            17 => ErrorKind::CantContinue,
+            19 => ErrorKind::NoResume,
+            20 => ErrorKind::ResumeWithoutError,
+            50 => ErrorKind::FieldOverflow,
+            52 => ErrorKind::BadFileNumber,
+            53 => ErrorKind::FileNotFound(String::new()),
+            54 => ErrorKind::BadFileMode,
+            55 => ErrorKind::FileAlreadyOpen,
+            57 => ErrorKind::DeviceIo(String::new()),
+            62 => ErrorKind::InputPastEnd,
+            63 => ErrorKind::BadRecordNumber,
+            70 => ErrorKind::PermissionDenied(String::new()),
+            _ => ErrorKind::Raised(code),
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let with_detail = |f: &mut fmt::Formatter, message: &str, detail: &str| {
+            if detail.is_empty() {
+                write!(f, "{}", message)
+            } else {
+                write!(f, "{}: {}", message, detail)
+            }
+        };
+
+        match self {
+            ErrorKind::Syntax(detail) => with_detail(f, "Syntax error", detail),
+            ErrorKind::OutOfData => write!(f, "Out of DATA"),
+            ErrorKind::IllegalFunctionCall(detail) => with_detail(f, "Illegal function call", detail),
+            ErrorKind::Overflow => write!(f, "Overflow"),
+            ErrorKind::LabelNotDefined(0) => write!(f, "Label not defined"),
+            ErrorKind::LabelNotDefined(line) => write!(f, "Label not defined: {}", line),
+            ErrorKind::DivisionByZero => write!(f, "Division by zero"),
+            ErrorKind::TypeMismatch(detail) => with_detail(f, "Type mismatch", detail),
+            ErrorKind::CantContinue => write!(f, "Can't continue"),
+            ErrorKind::NoResume => write!(f, "No RESUME"),
+            ErrorKind::ResumeWithoutError => write!(f, "RESUME without error"),
+            ErrorKind::FieldOverflow => write!(f, "FIELD overflow"),
+            ErrorKind::BadFileNumber => write!(f, "Bad file name or number"),
+            ErrorKind::FileNotFound(path) => with_detail(f, "File not found", path),
+            ErrorKind::BadFileMode => write!(f, "Bad file mode"),
+            ErrorKind::FileAlreadyOpen => write!(f, "File already open"),
+            ErrorKind::DeviceIo(detail) => with_detail(f, "Device I/O error", detail),
+            ErrorKind::InputPastEnd => write!(f, "Input past end of file"),
+            ErrorKind::BadRecordNumber => write!(f, "Bad record number"),
+            ErrorKind::PermissionDenied(path) => with_detail(f, "Permission denied", path),
+            ErrorKind::UndefinedVariable(name) => write!(f, "Undefined variable: {}", name),
+            ErrorKind::LimitExceeded(limit) => write!(f, "Exceeded {} limit", limit),
+            ErrorKind::Raised(_) => write!(f, "Unprintable error"),
+        }
+    }
+}
+
+/// An error that stopped the program, with the source line it occurred on.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RuntimeError {
+    pub kind: ErrorKind,
+    pub line: usize,
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} in line {}", self.kind, self.line)
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+/// A mistake in the program's source, found before it runs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyntaxError {
+    pub message: String,
+    pub span: Span,
+    /// A suggestion for fixing the mistake, such as a keyword that was
+    /// probably misspelled.
+    pub help: Option<String>,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.span.col {
+            0 => write!(f, "{} in line {}", self.message, self.span.line),
+            col => write!(f, "{} at line {}, column {}", self.message, self.span.line, col),
+        }
+    }
+}
+
+impl std::error::Error for SyntaxError {}
@@ -0,0 +1,306 @@
+use std::{collections::HashMap, io::{self, Read, Seek, SeekFrom, Write}};
+
+use crate::{
+    error::ErrorKind,
+    fs::Stream,
+    interpreter::{Value, VarType},
+};
+
+/// A file opened by `OPEN`, kept in the interpreter under its file number.
+pub enum OpenFile {
+    /// Read completely when opened; `pos` is the next unread byte.
+    Input { data: Vec<u8>, pos: usize },
+    Output(Box<dyn Stream>),
+    Random {
+        stream: Box<dyn Stream>,
+        record_len: usize,
+        /// The current record, which `FIELD` variables are slices of.
+        buffer: Vec<u8>,
+        fields: Vec<Field>,
+        /// The record last read or written, so that `GET` and `PUT`
+        /// without a record number move on to the next one.
+        record: u64,
+    },
+    Binary(Box<dyn Stream>),
+}
+
+/// A string variable bound to part of a random file's record by `FIELD`.
+pub struct Field {
+    pub variable: String,
+    pub offset: usize,
+    pub width: usize,
+}
+
+fn io_error(error: io::Error) -> ErrorKind {
+    ErrorKind::DeviceIo(error.to_string())
+}
+
+/// Seeks to item `number`, counted from 1, of a file made of `size`-byte
+/// items. Positions too far out for a seek are bad record numbers.
+fn seek_to(stream: &mut Box<dyn Stream>, number: u64, size: usize) -> Result<(), ErrorKind> {
+    let offset = (number - 1).checked_mul(size as u64)
+        .filter(|&offset| i64::try_from(offset).is_ok())
+        .ok_or(ErrorKind::BadRecordNumber)?;
+
+    stream.seek(SeekFrom::Start(offset)).map_err(io_error)?;
+    Ok(())
+}
+
+impl OpenFile {
+    pub fn eof(&mut self) -> Result<bool, ErrorKind> {
+        let stream = match self {
+            OpenFile::Input { data, pos } => return Ok(*pos >= data.len()),
+            OpenFile::Output(_) => return Err(ErrorKind::BadFileMode),
+            OpenFile::Random { stream, .. } | OpenFile::Binary(stream) => stream,
+        };
+
+        let pos = stream.stream_position().map_err(io_error)?;
+        let len = stream.seek(SeekFrom::End(0)).map_err(io_error)?;
+        stream.seek(SeekFrom::Start(pos)).map_err(io_error)?;
+        Ok(pos >= len)
+    }
+
+    /// Reads a record into the buffer; parts past the end of the file read
+    /// as zero bytes.
+    pub fn read_record(&mut self, number: Option<u64>) -> Result<(), ErrorKind> {
+        let OpenFile::Random { stream, record_len, buffer, record, .. } = self else {
+            return Err(ErrorKind::BadFileMode);
+        };
+
+        let number = number.unwrap_or(*record + 1);
+        seek_to(stream, number, *record_len)?;
+        buffer.fill(0);
+        read_up_to(stream, buffer)?;
+        *record = number;
+        Ok(())
+    }
+
+    pub fn write_record(&mut self, number: Option<u64>) -> Result<(), ErrorKind> {
+        let OpenFile::Random { stream, record_len, buffer, record, .. } = self else {
+            return Err(ErrorKind::BadFileMode);
+        };
+
+        let number = number.unwrap_or(*record + 1);
+        seek_to(stream, number, *record_len)?;
+        stream.write_all(buffer).map_err(io_error)?;
+        *record = number;
+        Ok(())
+    }
+
+    /// Reads `len` bytes of a binary file at a byte position counted from
+    /// 1, or at the current position.
+    pub fn read_bytes(&mut self, position: Option<u64>, len: usize) -> Result<Vec<u8>, ErrorKind> {
+        let OpenFile::Binary(stream) = self else {
+            return Err(ErrorKind::BadFileMode);
+        };
+
+        if let Some(position) = position {
+            seek_to(stream, position, 1)?;
+        }
+        let mut bytes = vec![0; len];
+        read_up_to(stream, &mut bytes)?;
+        Ok(bytes)
+    }
+
+    pub fn write_bytes(&mut self, position: Option<u64>, bytes: &[u8]) -> Result<(), ErrorKind> {
+        let OpenFile::Binary(stream) = self else {
+            return Err(ErrorKind::BadFileMode);
+        };
+
+        if let Some(position) = position {
+            seek_to(stream, position, 1)?;
+        }
+        stream.write_all(bytes).map_err(io_error)
+    }
+
+    pub fn set_fields(&mut self, layout: Vec<Field>) -> Result<(), ErrorKind> {
+        let OpenFile::Random { record_len, fields, .. } = self else {
+            return Err(ErrorKind::BadFileMode);
+        };
+
+        if layout.iter().map(|field| field.width).sum::<usize>() > *record_len {
+            return Err(ErrorKind::FieldOverflow);
+        }
+        *fields = layout;
+        Ok(())
+    }
+
+    /// The width of `variable` if `FIELD` bound it to this file.
+    pub fn field_width(&self, variable: &str) -> Option<usize> {
+        match self {
+            OpenFile::Random { fields, .. } => fields.iter().find(|f| f.variable == variable).map(|f| f.width),
+            _ => None,
+        }
+    }
+
+    /// The current contents of each `FIELD` variable.
+    pub fn field_values(&self) -> Vec<(String, Value)> {
+        match self {
+            OpenFile::Random { buffer, fields, .. } => fields.iter()
+                .map(|f| (f.variable.clone(), Value::String(latin1(&buffer[f.offset..f.offset + f.width]))))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Copies the `FIELD` variables into the record buffer, padding each
+    /// with spaces or cutting it to its width.
+    pub fn fill_fields(&mut self, variables: &HashMap<String, Value>) -> Result<(), ErrorKind> {
+        let OpenFile::Random { buffer, fields, .. } = self else {
+            return Err(ErrorKind::BadFileMode);
+        };
+
+        for field in fields {
+            let mut data = match variables.get(&field.variable) {
+                Some(Value::String(s)) => bytes(s)?,
+                _ => Vec::new(),
+            };
+            data.resize(field.width, b' ');
+            buffer[field.offset..field.offset + field.width].copy_from_slice(&data);
+        }
+        Ok(())
+    }
+
+    /// The start of the record buffer, for `GET` into a variable.
+    pub fn record(&self, len: usize) -> Result<&[u8], ErrorKind> {
+        match self {
+            OpenFile::Random { buffer, .. } => Ok(&buffer[..len.min(buffer.len())]),
+            _ => Err(ErrorKind::BadFileMode),
+        }
+    }
+
+    /// Overwrites the start of the record buffer, for `PUT` from a variable.
+    pub fn set_record(&mut self, data: &[u8]) -> Result<(), ErrorKind> {
+        let OpenFile::Random { buffer, .. } = self else {
+            return Err(ErrorKind::BadFileMode);
+        };
+        if data.len() > buffer.len() {
+            return Err(ErrorKind::FieldOverflow);
+        }
+
+        buffer[..data.len()].copy_from_slice(data);
+        Ok(())
+    }
+
+    pub fn write_line(&mut self, text: &str) -> Result<(), ErrorKind> {
+        let OpenFile::Output(stream) = self else {
+            return Err(ErrorKind::BadFileMode);
+        };
+
+        writeln!(stream, "{}", text).map_err(io_error)
+    }
+
+    /// Reads up to the end of the line, dropping the line ending.
+    pub fn read_line(&mut self) -> Result<String, ErrorKind> {
+        let OpenFile::Input { data, pos } = self else {
+            return Err(ErrorKind::BadFileMode);
+        };
+        if *pos >= data.len() {
+            return Err(ErrorKind::InputPastEnd);
+        }
+
+        let rest = &data[*pos..];
+        let end = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+        *pos += (end + 1).min(rest.len());
+
+        let line = rest[..end].strip_suffix(b"\r").unwrap_or(&rest[..end]);
+        Ok(String::from_utf8_lossy(line).into_owned())
+    }
+
+    /// Reads one `INPUT #` field: either a quoted string or everything up
+    /// to the next comma or line ending, with surrounding blanks removed.
+    pub fn read_field(&mut self) -> Result<String, ErrorKind> {
+        let OpenFile::Input { data, pos } = self else {
+            return Err(ErrorKind::BadFileMode);
+        };
+
+        while *pos < data.len() && data[*pos].is_ascii_whitespace() {
+            *pos += 1;
+        }
+        if *pos >= data.len() {
+            return Err(ErrorKind::InputPastEnd);
+        }
+
+        let field = if data[*pos] == b'"' {
+            let start = *pos + 1;
+            let len = data[start..].iter().position(|&b| b == b'"').unwrap_or(data.len() - start);
+            *pos = start + len + 1;
+            &data[start..start + len]
+        } else {
+            let start = *pos;
+            let len = data[start..].iter().position(|&b| b == b',' || b == b'\n').unwrap_or(data.len() - start);
+            *pos = start + len;
+            data[start..start + len].trim_ascii_end()
+        };
+        let field = String::from_utf8_lossy(field).into_owned();
+
+        while *pos < data.len() && matches!(data[*pos], b' ' | b'\t' | b'\r') {
+            *pos += 1;
+        }
+        if *pos < data.len() && matches!(data[*pos], b',' | b'\n') {
+            *pos += 1;
+        }
+
+        Ok(field)
+    }
+}
+
+/// Fills as much of `buf` as the stream has left.
+fn read_up_to(stream: &mut Box<dyn Stream>, buf: &mut [u8]) -> Result<(), ErrorKind> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match stream.read(&mut buf[filled..]).map_err(io_error)? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(())
+}
+
+/// Binary data lives in BASIC strings one byte per character, as Latin-1.
+pub fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+pub fn bytes(s: &str) -> Result<Vec<u8>, ErrorKind> {
+    s.chars()
+        .map(|c| u8::try_from(c).map_err(|_| ErrorKind::IllegalFunctionCall(format!("character {:?} is not a byte", c))))
+        .collect()
+}
+
+/// Size in bytes of a numeric type when stored in a file.
+pub fn width(ty: VarType) -> Option<usize> {
+    match ty {
+        VarType::String => None,
+        VarType::Integer => Some(2),
+        VarType::Long | VarType::Single => Some(4),
+        VarType::Double => Some(8),
+    }
+}
+
+/// Encodes a value in the little-endian layout `MKI$` and friends use.
+pub fn pack(ty: VarType, name: &str, val: Value) -> Result<Vec<u8>, ErrorKind> {
+    Ok(match ty.coerce(name, val)? {
+        Value::String(s) => bytes(&s)?,
+        Value::Integer(n) if ty == VarType::Integer => (n as i16).to_le_bytes().to_vec(),
+        Value::Integer(n) => (n as i32).to_le_bytes().to_vec(),
+        Value::Single(f) => f.to_le_bytes().to_vec(),
+        Value::Float(f) => f.to_le_bytes().to_vec(),
+        Value::Bool(_) => unreachable!("coerced values are never booleans"),
+    })
+}
+
+/// Decodes bytes written by `pack`; missing trailing bytes count as zero.
+pub fn unpack(ty: VarType, bytes: &[u8]) -> Value {
+    let mut buf = [0; 8];
+    let n = bytes.len().min(8);
+    buf[..n].copy_from_slice(&bytes[..n]);
+
+    match ty {
+        VarType::String => Value::String(latin1(bytes)),
+        VarType::Integer => Value::Integer(i16::from_le_bytes([buf[0], buf[1]]) as i64),
+        VarType::Long => Value::Integer(i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as i64),
+        VarType::Single => Value::Single(f32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]])),
+        VarType::Double => Value::Float(f64::from_le_bytes(buf)),
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs::OpenOptions,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Component, Path, PathBuf},
+    rc::Rc,
+};
+
+/// A byte stream opened through a `FileSystem`.
+pub trait Stream: Read + Write + Seek {}
+
+impl<T: Read + Write + Seek> Stream for T {}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OpenMode {
+    /// Read an existing file from the start.
+    Read,
+    /// Create or truncate a file and write to it.
+    Write,
+    /// Create a file if needed and write at its end.
+    Append,
+    /// Create a file if needed and read or write anywhere in it.
+    ReadWrite,
+}
+
+/// Where `OPEN` finds files. Hosts implement this to sandbox scripts or to
+/// keep their files in memory.
+pub trait FileSystem {
+    fn open(&mut self, path: &str, mode: OpenMode) -> io::Result<Box<dyn Stream>>;
+}
+
+/// The host's real filesystem, optionally confined to one directory.
+#[derive(Clone, Debug, Default)]
+pub struct NativeFileSystem {
+    root: Option<PathBuf>,
+}
+
+impl NativeFileSystem {
+    pub fn new() -> NativeFileSystem {
+        NativeFileSystem { root: None }
+    }
+
+    /// Resolves every path against `root` and refuses absolute paths and
+    /// paths that climb out of it with `..`.
+    pub fn sandboxed(root: impl Into<PathBuf>) -> NativeFileSystem {
+        NativeFileSystem { root: Some(root.into()) }
+    }
+
+    fn resolve(&self, path: &str) -> io::Result<PathBuf> {
+        let Some(root) = &self.root else {
+            return Ok(PathBuf::from(path));
+        };
+
+        let escapes = Path::new(path).components()
+            .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir));
+        if escapes {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "path outside of sandbox"));
+        }
+
+        Ok(root.join(path))
+    }
+}
+
+impl FileSystem for NativeFileSystem {
+    fn open(&mut self, path: &str, mode: OpenMode) -> io::Result<Box<dyn Stream>> {
+        let mut options = OpenOptions::new();
+        match mode {
+            OpenMode::Read => options.read(true),
+            OpenMode::Write => options.write(true).create(true).truncate(true),
+            OpenMode::Append => options.append(true).create(true),
+            OpenMode::ReadWrite => options.read(true).write(true).create(true).truncate(false),
+        };
+
+        Ok(Box::new(options.open(self.resolve(path)?)?))
+    }
+}
+
+type SharedBytes = Rc<RefCell<Vec<u8>>>;
+
+/// Files kept in memory. Clones share the same files, so a host can keep
+/// one clone to seed inputs and inspect what a program wrote.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryFileSystem {
+    files: Rc<RefCell<HashMap<String, SharedBytes>>>,
+}
+
+impl MemoryFileSystem {
+    pub fn new() -> MemoryFileSystem {
+        MemoryFileSystem::default()
+    }
+
+    pub fn insert(&self, path: &str, contents: impl Into<Vec<u8>>) {
+        self.files.borrow_mut().insert(path.to_string(), Rc::new(RefCell::new(contents.into())));
+    }
+
+    pub fn contents(&self, path: &str) -> Option<Vec<u8>> {
+        self.files.borrow().get(path).map(|data| data.borrow().clone())
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn open(&mut self, path: &str, mode: OpenMode) -> io::Result<Box<dyn Stream>> {
+        let mut files = self.files.borrow_mut();
+
+        let data = match mode {
+            OpenMode::Read => files.get(path).cloned()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "file not found"))?,
+            OpenMode::Write => {
+                let data = Rc::new(RefCell::new(Vec::new()));
+                files.insert(path.to_string(), data.clone());
+                data
+            }
+            OpenMode::Append | OpenMode::ReadWrite => files.entry(path.to_string()).or_default().clone(),
+        };
+
+        let pos = if mode == OpenMode::Append { data.borrow().len() as u64 } else { 0 };
+        Ok(Box::new(MemoryFile { data, pos }))
+    }
+}
+
+struct MemoryFile {
+    data: SharedBytes,
+    pos: u64,
+}
+
+impl Read for MemoryFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let data = self.data.borrow();
+        let start = (self.pos as usize).min(data.len());
+        let n = buf.len().min(data.len() - start);
+
+        buf[..n].copy_from_slice(&data[start..start + n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Write for MemoryFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut data = self.data.borrow_mut();
+        let too_large = || io::Error::new(io::ErrorKind::OutOfMemory, "file too large for memory");
+        let start = usize::try_from(self.pos).map_err(|_| too_large())?;
+        let end = start.checked_add(buf.len()).ok_or_else(too_large)?;
+
+        if data.len() < end {
+            let extra = end - data.len();
+            data.try_reserve(extra).map_err(|_| too_large())?;
+            data.resize(end, 0);
+        }
+        data[start..end].copy_from_slice(buf);
+        self.pos = end as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for MemoryFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let len = self.data.borrow().len() as i64;
+        let target = match pos {
+            SeekFrom::Start(n) => n as i64,
+            SeekFrom::End(n) => len + n,
+            SeekFrom::Current(n) => self.pos as i64 + n,
+        };
+
+        if target < 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "seek before start of file"));
+        }
+        self.pos = target as u64;
+        Ok(self.pos)
+    }
+}
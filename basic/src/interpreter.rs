@@ -1,216 +1,1497 @@
 use core::panic;
-use std::{collections::HashMap, io};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, VecDeque},
+    fmt,
+    io::{self, Read},
+    sync::{atomic::{AtomicBool, Ordering as AtomicOrdering}, Arc},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
-use crate::{lexer::Token, parser::Expr};
+use crate::{
+    dialect::{Dialect, Overflow, Truth},
+    error::{ErrorKind, RuntimeError},
+    files::{self, Field, OpenFile},
+    fs::{FileSystem, NativeFileSystem, OpenMode},
+    lexer::Token,
+    limits::{Limit, Limits},
+    parser::{Case, CaseTest, Expr, FileMode, ResumeAt},
+    rng::Rng,
+};
+
+/// Seed used until the program or the host calls `RANDOMIZE`/`set_seed`, so
+/// that `RND` repeats between runs like it does in classic BASIC.
+const DEFAULT_SEED: u64 = 0x5EED;
 
 #[derive(Clone, Debug)]
 pub enum Value {
     Integer(i64),
+    Single(f32),
     Float(f64),
     String(String),
     Bool(bool),
 }
 
+impl Value {
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Integer(n) => Some(*n as f64),
+            Value::Single(f) => Some(*f as f64),
+            Value::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    /// Rounds to the nearest integer the way BASIC does when a float is
+    /// stored in an integer variable.
+    fn round(f: f64) -> Result<i64, ErrorKind> {
+        let rounded = f.round();
+        if rounded < i64::MIN as f64 || rounded >= i64::MAX as f64 || rounded.is_nan() {
+            return Err(ErrorKind::Overflow);
+        }
+        Ok(rounded as i64)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(n: i64) -> Value {
+        Value::Integer(n)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(n: i32) -> Value {
+        Value::Integer(n as i64)
+    }
+}
+
+impl From<f32> for Value {
+    fn from(f: f32) -> Value {
+        Value::Single(f)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(f: f64) -> Value {
+        Value::Float(f)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Value {
+        Value::String(s)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Value {
+        Value::Bool(b)
+    }
+}
+
+/// Floats round to the nearest integer, as when stored in an integer
+/// variable.
+impl TryFrom<Value> for i64 {
+    type Error = ErrorKind;
+
+    fn try_from(val: Value) -> Result<i64, ErrorKind> {
+        match val {
+            Value::Integer(n) => Ok(n),
+            Value::Single(f) => Value::round(f as f64),
+            Value::Float(f) => Value::round(f),
+            _ => Err(ErrorKind::TypeMismatch("expected a number".to_string())),
+        }
+    }
+}
+
+impl TryFrom<Value> for i32 {
+    type Error = ErrorKind;
+
+    fn try_from(val: Value) -> Result<i32, ErrorKind> {
+        i32::try_from(i64::try_from(val)?).map_err(|_| ErrorKind::Overflow)
+    }
+}
+
+impl TryFrom<Value> for f64 {
+    type Error = ErrorKind;
+
+    fn try_from(val: Value) -> Result<f64, ErrorKind> {
+        val.as_f64().ok_or_else(|| ErrorKind::TypeMismatch("expected a number".to_string()))
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = ErrorKind;
+
+    fn try_from(val: Value) -> Result<String, ErrorKind> {
+        match val {
+            Value::String(s) => Ok(s),
+            _ => Err(ErrorKind::TypeMismatch("expected a string".to_string())),
+        }
+    }
+}
+
+/// Numbers are true when non-zero, as in the classic truth dialect.
+impl TryFrom<Value> for bool {
+    type Error = ErrorKind;
+
+    fn try_from(val: Value) -> Result<bool, ErrorKind> {
+        match val {
+            Value::Bool(b) => Ok(b),
+            Value::Integer(n) => Ok(n != 0),
+            Value::Single(f) => Ok(f != 0.0),
+            Value::Float(f) => Ok(f != 0.0),
+            Value::String(_) => Err(ErrorKind::TypeMismatch("expected a boolean".to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Integer(n) => write!(f, "{}", n),
+            // Shown with f32's own formatting, so `0.1!` prints as `0.1`
+            // rather than the digits it gains when widened.
+            Value::Single(x) => write!(f, "{}", x),
+            Value::Float(x) => write!(f, "{}", x),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Bool(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+/// The static type of a variable, given by its trailing type sigil.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VarType {
+    String,
+    Integer,
+    Single,
+    Double,
+    Long,
+}
+
+impl VarType {
+    pub fn of(name: &str) -> Option<VarType> {
+        match name.chars().last()? {
+            '$' => Some(VarType::String),
+            '%' => Some(VarType::Integer),
+            '!' => Some(VarType::Single),
+            '#' => Some(VarType::Double),
+            '&' => Some(VarType::Long),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn coerce(self, name: &str, val: Value) -> Result<Value, ErrorKind> {
+        Ok(match (self, val) {
+            (VarType::String, Value::String(s)) => Value::String(s),
+            (VarType::String, _) | (_, Value::String(_)) => {
+                return Err(ErrorKind::TypeMismatch(format!("variable {}", name)));
+            }
+            (VarType::Integer, Value::Integer(n)) if i16::try_from(n).is_ok() => Value::Integer(n),
+            (VarType::Long, Value::Integer(n)) if i32::try_from(n).is_ok() => Value::Integer(n),
+            (VarType::Integer | VarType::Long, Value::Integer(_)) => return Err(ErrorKind::Overflow),
+            (VarType::Integer | VarType::Long, Value::Single(f)) => self.coerce(name, Value::Integer(Value::round(f as f64)?))?,
+            (VarType::Integer | VarType::Long, Value::Float(f)) => self.coerce(name, Value::Integer(Value::round(f)?))?,
+            (VarType::Single, Value::Integer(n)) => Value::Single(n as f32),
+            (VarType::Single, Value::Single(f)) => Value::Single(f),
+            (VarType::Single, Value::Float(f)) => Value::Single(f as f32),
+            (VarType::Double, Value::Single(f)) => Value::Float(f as f64),
+            (VarType::Double, Value::Integer(n)) => Value::Float(n as f64),
+            (VarType::Double, Value::Float(f)) => Value::Float(f),
+            (_, Value::Bool(b)) => self.coerce(name, Value::Integer(if b { -1 } else { 0 }))?,
+        })
+    }
+}
+
+/// How a run of the interpreter ended without an error.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Completion {
+    /// The program ran off its end or executed `END`.
+    Finished,
+    /// The program executed `STOP` on the given line; `cont` resumes it.
+    Break(usize),
+}
+
+/// What a single `step` of the interpreter did.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StepResult {
+    /// A statement ran and the program has more to do.
+    Running,
+    /// `INPUT` is waiting for `provide_input`; the prompt is for the host
+    /// to show.
+    NeedsInput(String),
+    /// Text the program printed, including its line ending.
+    Output(String),
+    Finished,
+    /// The program stopped on the given line; `begin_cont` resumes it.
+    Break(usize),
+}
+
+/// Interrupts a running interpreter from another thread, such as a signal
+/// handler. Clones share the same flag.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// Asks the interpreter to break before its next statement, as if the
+    /// program had executed `STOP`.
+    pub fn cancel(&self) {
+        self.0.store(true, AtomicOrdering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(AtomicOrdering::SeqCst)
+    }
+
+    fn take(&self) -> bool {
+        self.0.swap(false, AtomicOrdering::SeqCst)
+    }
+}
+
+/// A block of statements being executed, with the index of the next one.
+#[derive(Clone, Copy, Debug)]
+struct Frame<'a> {
+    stmts: &'a [Expr],
+    pc: usize,
+    kind: FrameKind<'a>,
+    /// Source line of the statement that opened the block.
+    line: usize,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum FrameKind<'a> {
+    Program,
+    If,
+    Select,
+    For { variable: &'a str, counter: i64, end: i64, step: i64 },
+}
+
+impl<'a> Frame<'a> {
+    fn new(stmts: &'a [Expr], kind: FrameKind<'a>, line: usize) -> Frame<'a> {
+        Frame { stmts, pc: 0, kind, line }
+    }
+}
+
+/// One level of `Interpreter::call_stack`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StackFrame {
+    /// `main` for the program itself, otherwise the statement that opened
+    /// the block, such as `FOR i`.
+    pub name: String,
+    /// The line the block is executing, or about to execute.
+    pub line: usize,
+}
+
+/// Execution state saved by `STOP`, for `cont` to pick up.
+struct Stopped<'a> {
+    frames: Vec<Frame<'a>>,
+    line: usize,
+    /// What the run had used of the limits, which carries over when it
+    /// continues.
+    executed: u64,
+    elapsed: Duration,
+    /// The program's `ON ERROR` state, put aside while direct-mode lines
+    /// run.
+    on_error: Option<usize>,
+    trapped: Option<Trapped<'a>>,
+}
+
+/// Where to continue after `RESUME`, captured when an error was trapped.
+struct Trapped<'a> {
+    error: RuntimeError,
+    frames: Vec<Frame<'a>>,
+    /// The error came from finishing a block (a `FOR` loop's `NEXT`)
+    /// rather than from a statement.
+    at_frame_end: bool,
+}
+
+type NativeBody = dyn Fn(&[Value]) -> Result<Value, ErrorKind>;
+
+/// A Rust function registered with `register_fn`.
+struct NativeFn {
+    params: Vec<VarType>,
+    func: Box<NativeBody>,
+}
+
 pub struct Interpreter<'a> {
     exprs: &'a [Expr],
     variables: HashMap<String, Value>,
+    dialect: Dialect,
+    rng: Rng,
+    last_rnd: f64,
+    fixed_seed: Option<u64>,
+    /// Every `DATA` constant in the program with its source line, in order.
+    data: Vec<(usize, Value)>,
+    data_ptr: usize,
+    frames: Vec<Frame<'a>>,
+    line: usize,
+    on_error: Option<usize>,
+    trapped: Option<Trapped<'a>>,
+    stopped: Option<Stopped<'a>>,
+    stop_requested: bool,
+    fs: Box<dyn FileSystem>,
+    files: HashMap<i64, OpenFile>,
+    natives: HashMap<String, NativeFn>,
+    limits: Limits,
+    /// Bytes of string data currently held in variables.
+    memory: usize,
+    cancel: CancelToken,
+    /// Printed text that `step` has not handed to the host yet.
+    output: VecDeque<String>,
+    /// A line for the next `INPUT`, from `provide_input`.
+    input: Option<String>,
+    /// The prompt of an `INPUT` that suspended for lack of input.
+    awaiting_input: Option<String>,
+    /// Statements executed and time spent in `step` since the run began,
+    /// for the limits. `Expr::Line` markers and the ends of blocks are not
+    /// statements.
+    executed: u64,
+    elapsed: Duration,
 }
 
 impl<'a> Interpreter<'a> {
     pub fn new(exprs: &'a [Expr]) -> Self {
-        Interpreter { 
+        Interpreter::with_dialect(exprs, Dialect::default())
+    }
+
+    pub fn with_dialect(exprs: &'a [Expr], dialect: Dialect) -> Self {
+        let mut data = Vec::new();
+        Interpreter::collect_data(exprs, &mut data);
+
+        Interpreter {
             exprs,
             variables: HashMap::new(),
+            dialect,
+            rng: Rng::new(DEFAULT_SEED),
+            last_rnd: 0.0,
+            fixed_seed: None,
+            data,
+            data_ptr: 0,
+            frames: Vec::new(),
+            line: 0,
+            on_error: None,
+            trapped: None,
+            stopped: None,
+            stop_requested: false,
+            fs: Box::new(NativeFileSystem::new()),
+            files: HashMap::new(),
+            natives: HashMap::new(),
+            limits: Limits::default(),
+            memory: 0,
+            cancel: CancelToken::default(),
+            output: VecDeque::new(),
+            input: None,
+            awaiting_input: None,
+            executed: 0,
+            elapsed: Duration::ZERO,
         }
     }
 
-    pub fn interpret(&mut self) {
-        for expr in self.exprs {
-            self.eval_stmt(expr);
+    fn collect_data(exprs: &[Expr], data: &mut Vec<(usize, Value)>) {
+        for expr in exprs {
+            match expr {
+                Expr::Data(line, values) => {
+                    for value in values {
+                        let value = match value {
+                            Expr::Num(n) => Value::Integer(*n),
+                            Expr::Single(f) => Value::Single(*f),
+                            Expr::Float(f) => Value::Float(*f),
+                            Expr::Neg(n) => match n.as_ref() {
+                                Expr::Num(n) => Value::Integer(-n),
+                                Expr::Single(f) => Value::Single(-f),
+                                Expr::Float(f) => Value::Float(-f),
+                                _ => panic!("Invalid constant in DATA statement"),
+                            },
+                            Expr::Str(s) => Value::String(s.clone()),
+                            _ => panic!("Invalid constant in DATA statement"),
+                        };
+                        data.push((*line, value));
+                    }
+                }
+                Expr::For { body, .. } => Interpreter::collect_data(body, data),
+                Expr::If(_, cons) => Interpreter::collect_data(cons, data),
+                Expr::Select { cases, default, .. } => {
+                    for case in cases {
+                        Interpreter::collect_data(&case.body, data);
+                    }
+                    if let Some(default) = default {
+                        Interpreter::collect_data(default, data);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Reseeds the random number generator and pins the seed, so that a bare
+    /// `RANDOMIZE` in the program reuses it instead of the clock.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.fixed_seed = Some(seed);
+        self.rng = Rng::new(seed);
+    }
+
+    /// Replaces where `OPEN` finds files, which is the real filesystem by
+    /// default. Files that are already open stay open.
+    pub fn set_file_system(&mut self, fs: impl FileSystem + 'static) {
+        self.fs = Box::new(fs);
+    }
+
+    /// Makes a Rust function callable from BASIC as `NAME(arg, ...)`. Names
+    /// are case-insensitive and built-in functions take precedence. Each
+    /// argument is converted to the matching entry of `params`, so the
+    /// call's arity and argument types are checked before `func` runs.
+    pub fn register_fn<F>(&mut self, name: &str, params: &[VarType], func: F)
+    where
+        F: Fn(&[Value]) -> Result<Value, ErrorKind> + 'static,
+    {
+        let native = NativeFn { params: params.to_vec(), func: Box::new(func) };
+        self.natives.insert(name.to_uppercase(), native);
+    }
+
+    /// Returns a token that breaks the running program when cancelled.
+    /// Each call of `interpret`, `cont` or `run_immediate` starts by
+    /// clearing it, so a cancel only affects the run in progress.
+    pub fn cancel_token(&self) -> CancelToken {
+        self.cancel.clone()
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Sets a variable before or between runs, converting the value to the
+    /// variable's type as an assignment in BASIC would.
+    pub fn set_var(&mut self, name: &str, val: impl Into<Value>) -> Result<(), ErrorKind> {
+        self.store(name, val.into())
+    }
+
+    pub fn get_var(&self, name: &str) -> Option<&Value> {
+        self.variables.get(name)
+    }
+
+    /// Iterates over every variable the program or host has set, in no
+    /// particular order.
+    pub fn vars(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.variables.iter().map(|(name, val)| (name.as_str(), val))
+    }
+
+    /// Runs the program from the start until it finishes or stops,
+    /// printing to stdout and reading `INPUT` from stdin.
+    pub fn interpret(&mut self) -> Result<Completion, RuntimeError> {
+        self.begin();
+        self.run()
+    }
+
+    /// Prepares to run the program from the start with `step`.
+    pub fn begin(&mut self) {
+        self.stopped = None;
+        self.on_error = None;
+        self.trapped = None;
+        self.files.clear();
+        self.rng = Rng::new(self.fixed_seed.unwrap_or(DEFAULT_SEED));
+        self.last_rnd = 0.0;
+        self.data_ptr = 0;
+        self.start(vec![Frame::new(self.exprs, FrameKind::Program, 0)]);
+    }
+
+    fn start(&mut self, frames: Vec<Frame<'a>>) {
+        self.frames = frames;
+        self.output.clear();
+        self.input = None;
+        self.awaiting_input = None;
+        self.executed = 0;
+        self.elapsed = Duration::ZERO;
+        self.cancel.take();
+    }
+
+    /// Replaces the program, as `RUN` does: variables, `DATA` and any
+    /// stopped state are reset, while the dialect and seed settings are kept.
+    pub fn load(&mut self, exprs: &'a [Expr]) {
+        let mut data = Vec::new();
+        Interpreter::collect_data(exprs, &mut data);
+
+        self.exprs = exprs;
+        self.variables.clear();
+        self.memory = 0;
+        self.data = data;
+        self.data_ptr = 0;
+        self.frames.clear();
+        self.on_error = None;
+        self.trapped = None;
+        self.stopped = None;
+        self.files.clear();
+    }
+
+    /// Runs statements in direct mode, as typed at the REPL. They share the
+    /// program's variables and leave a stopped program resumable.
+    /// The program's `ON ERROR` handler does not apply to them.
+    pub fn run_immediate(&mut self, exprs: &'a [Expr]) -> Result<Completion, RuntimeError> {
+        self.begin_immediate(exprs);
+        self.run()
+    }
+
+    /// Prepares to run direct-mode statements with `step`.
+    pub fn begin_immediate(&mut self, exprs: &'a [Expr]) {
+        self.on_error = None;
+        self.trapped = None;
+        self.start(vec![Frame::new(exprs, FrameKind::Program, 0)]);
+    }
+
+    /// Resumes a program paused by `STOP`.
+    pub fn cont(&mut self) -> Result<Completion, RuntimeError> {
+        self.begin_cont()?;
+        self.run()
+    }
+
+    /// Prepares to resume a program paused by `STOP` with `step`.
+    pub fn begin_cont(&mut self) -> Result<(), RuntimeError> {
+        let stopped = self.stopped.take()
+            .ok_or(RuntimeError { kind: ErrorKind::CantContinue, line: self.line })?;
+
+        self.start(stopped.frames);
+        self.line = stopped.line;
+        self.executed = stopped.executed;
+        self.elapsed = stopped.elapsed;
+        self.on_error = stopped.on_error;
+        self.trapped = stopped.trapped;
+        Ok(())
+    }
+
+    /// Answers the `INPUT` that made `step` return `NeedsInput`.
+    pub fn provide_input(&mut self, line: impl Into<String>) {
+        self.input = Some(line.into());
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.is_some()
+    }
+
+    /// The source line of the statement executed last.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// The line about to start, if the next statement is the first one on
+    /// its source line.
+    pub fn next_line(&self) -> Option<usize> {
+        let frame = self.frames.last()?;
+        match frame.stmts.get(frame.pc)? {
+            Expr::Line(line) => Some(*line),
+            _ => None,
         }
     }
 
-    fn eval_stmt(&mut self, expr: &Expr) {
+    /// How many blocks are open, counting the program itself.
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// The open blocks, innermost first.
+    pub fn call_stack(&self) -> Vec<StackFrame> {
+        let mut stack = Vec::new();
+        let mut line = self.next_line().unwrap_or(self.line);
+
+        for frame in self.frames.iter().rev() {
+            let name = match frame.kind {
+                FrameKind::Program => "main".to_string(),
+                FrameKind::If => "IF".to_string(),
+                FrameKind::Select => "SELECT CASE".to_string(),
+                FrameKind::For { variable, .. } => format!("FOR {}", variable),
+            };
+            stack.push(StackFrame { name, line });
+            line = frame.line;
+        }
+
+        stack
+    }
+
+    /// Evaluates an expression against the program's variables, as a
+    /// debugger watch does.
+    pub fn evaluate(&mut self, expr: &Expr) -> Result<Value, ErrorKind> {
+        self.eval_expr(expr)
+    }
+
+    /// Steps the program to completion on the console.
+    fn run(&mut self) -> Result<Completion, RuntimeError> {
+        loop {
+            match self.step()? {
+                StepResult::Running => {}
+                StepResult::Output(text) => print!("{}", text),
+                StepResult::NeedsInput(prompt) => {
+                    println!("{}", prompt);
+
+                    let mut line = String::new();
+                    io::stdin().read_line(&mut line).expect("Failed to read line");
+                    self.provide_input(line.trim_end_matches(['\r', '\n']));
+                }
+                StepResult::Finished => return Ok(Completion::Finished),
+                StepResult::Break(line) => return Ok(Completion::Break(line)),
+            }
+        }
+    }
+
+    /// Removes the oldest printed text that `step` has not returned yet.
+    pub fn take_output(&mut self) -> Option<String> {
+        self.output.pop_front()
+    }
+
+    /// Hands over pending output, or executes the next statement. Hosts
+    /// call this in a loop after `begin`, `begin_cont` or
+    /// `begin_immediate`, and answer `NeedsInput` with `provide_input`
+    /// before stepping on.
+    pub fn step(&mut self) -> Result<StepResult, RuntimeError> {
+        if let Some(text) = self.take_output() {
+            return Ok(StepResult::Output(text));
+        }
+        if let Some(prompt) = &self.awaiting_input {
+            if self.input.is_none() {
+                return Ok(StepResult::NeedsInput(prompt.clone()));
+            }
+            self.awaiting_input = None;
+        }
+
+        if self.frames.is_empty() {
+            if let Some(trapped) = self.trapped.take() {
+                return Err(RuntimeError { kind: ErrorKind::NoResume, line: trapped.error.line });
+            }
+            return Ok(StepResult::Finished);
+        }
+
+        let started = Instant::now();
+        self.check_limits()?;
+
+        if let Err(kind) = self.exec_next() {
+            self.trap(kind)?;
+        }
+        self.elapsed += started.elapsed();
+
+        if self.stop_requested || self.cancel.take() {
+            self.stop_requested = false;
+            self.stopped = Some(Stopped {
+                frames: std::mem::take(&mut self.frames),
+                line: self.line,
+                executed: self.executed,
+                elapsed: self.elapsed,
+                on_error: self.on_error,
+                trapped: self.trapped.take(),
+            });
+            return Ok(StepResult::Break(self.line));
+        }
+        Ok(StepResult::Running)
+    }
+
+    fn check_limits(&self) -> Result<(), RuntimeError> {
+        // The program's own frame is not a nested block.
+        let depth = self.frames.len().saturating_sub(1);
+        let exceeded = if self.limits.depth.is_some_and(|max| depth > max) {
+            Limit::Depth
+        } else if self.limits.time.is_some_and(|max| self.elapsed > max) {
+            Limit::Time
+        } else {
+            return Ok(());
+        };
+
+        Err(RuntimeError { kind: ErrorKind::LimitExceeded(exceeded), line: self.line })
+    }
+
+    /// Sends an error to the `ON ERROR` handler, or returns it if there is
+    /// none, the error happened inside the handler itself, or the error is
+    /// one the program must not be able to recover from.
+    fn trap(&mut self, kind: ErrorKind) -> Result<(), RuntimeError> {
+        let trappable = !matches!(kind, ErrorKind::LimitExceeded(_));
+        let error = RuntimeError { kind, line: self.line };
+
+        let handler = match self.on_error {
+            Some(handler) if self.trapped.is_none() && trappable => handler,
+            _ => return Err(error),
+        };
+
+        let top = self.frames.last().expect("error raised outside of a block");
+        self.trapped = Some(Trapped {
+            at_frame_end: top.pc >= top.stmts.len(),
+            frames: self.frames.clone(),
+            error: error.clone(),
+        });
+
+        self.jump(handler).map_err(|kind| RuntimeError { kind, line: error.line })
+    }
+
+    /// Continues execution at the first statement of a top-level source line.
+    fn jump(&mut self, line: usize) -> Result<(), ErrorKind> {
+        let pc = self.exprs.iter()
+            .position(|expr| matches!(expr, Expr::Line(l) if *l == line))
+            .ok_or(ErrorKind::LabelNotDefined(line))?;
+
+        self.frames = vec![Frame { pc, ..Frame::new(self.exprs, FrameKind::Program, 0) }];
+        Ok(())
+    }
+
+    fn exec_next(&mut self) -> Result<(), ErrorKind> {
+        let frame = self.frames.last_mut().expect("no block to execute");
+
+        if frame.pc >= frame.stmts.len() {
+            return self.end_frame();
+        }
+
+        let stmt = &frame.stmts[frame.pc];
+        frame.pc += 1;
+
+        if !matches!(stmt, Expr::Line(_)) {
+            self.executed += 1;
+            if self.limits.statements.is_some_and(|max| self.executed > max) {
+                return Err(ErrorKind::LimitExceeded(Limit::Statements));
+            }
+        }
+        self.exec_stmt(stmt)
+    }
+
+    fn end_frame(&mut self) -> Result<(), ErrorKind> {
+        let frame = *self.frames.last().expect("no block to finish");
+
+        if let FrameKind::For { variable, counter, end, step } = frame.kind {
+            if let Some(next) = counter.checked_add(step) {
+                self.store(variable, Value::Integer(next))?;
+
+                if (step > 0 && next <= end) || (step < 0 && next >= end) {
+                    let top = self.frames.last_mut().expect("no block to finish");
+                    top.pc = 0;
+                    top.kind = FrameKind::For { variable, counter: next, end, step };
+                    return Ok(());
+                }
+            }
+        }
+
+        self.frames.pop();
+        Ok(())
+    }
+
+    fn store(&mut self, id: &str, val: Value) -> Result<(), ErrorKind> {
+        let val = match VarType::of(id) {
+            Some(ty) => ty.coerce(id, val)?,
+            None => val,
+        };
+
+        let size = |val: Option<&Value>| match val {
+            Some(Value::String(s)) => s.len(),
+            _ => 0,
+        };
+        let memory = self.memory - size(self.variables.get(id)) + size(Some(&val));
+        if self.limits.memory.is_some_and(|max| memory > max) {
+            return Err(ErrorKind::LimitExceeded(Limit::Memory));
+        }
+
+        self.memory = memory;
+        self.variables.insert(id.to_string(), val);
+        Ok(())
+    }
+
+    fn exec_stmt(&mut self, expr: &'a Expr) -> Result<(), ErrorKind> {
         match expr {
-            Expr::Print(ref expr) => self.print(expr),
-            Expr::Input(ref prompt, ref out) => self.input(prompt, out),
-            Expr::VarDec(ref id, ref expr) => self.var_dec(id, expr),
-            Expr::Assign(ref id, ref expr) => self.assign_expr(id, expr),
-            Expr::Bin(ref l, ref op, ref r) => { self.bin_expr(l, op, r); },
-            Expr::Rel(ref l, ref op, ref r) => { self.rel_expr(l, op, r); },
-            Expr::If(ref cond, ref cons) => self.if_stmt(cond, cons),
+            Expr::Line(line) => self.line = *line,
+            Expr::Print(ref expr) => self.print(expr)?,
+            Expr::Input(ref prompt, ref out) => self.input(prompt, out)?,
+            Expr::VarDec(ref id, ref expr) => self.var_dec(id, expr)?,
+            Expr::Assign(ref id, ref expr) => self.assign_expr(id, expr)?,
+            Expr::If(ref cond, ref cons) => self.if_stmt(cond, cons)?,
             Expr::For { ref variable, ref start, ref end, ref step, ref body } => {
-                self.for_loop(variable, start, end, step.as_deref(), body)
+                self.for_loop(variable, start, end, step.as_deref(), body)?
             },
-            _ => { self.eval_expr(expr); },
+            Expr::Randomize(ref seed) => self.randomize(seed.as_deref())?,
+            Expr::Data(..) => {},
+            Expr::Read(ref ids) => self.read(ids)?,
+            Expr::Restore(line) => self.restore(*line),
+            Expr::Select { ref subject, ref cases, ref default } => {
+                self.select(subject, cases, default.as_deref())?
+            },
+            Expr::OnError(handler) => self.on_error = *handler,
+            Expr::Resume(at) => self.resume(*at)?,
+            Expr::Raise(ref code) => self.raise(code)?,
+            Expr::End => {
+                self.frames.clear();
+                self.trapped = None;
+                self.stopped = None;
+                self.files.clear();
+            }
+            Expr::Stop => self.stop_requested = true,
+            Expr::Open { ref path, mode, ref number, ref len } => self.open(path, *mode, number, len.as_deref())?,
+            Expr::Close(ref numbers) => self.close(numbers)?,
+            Expr::PrintFile(ref number, ref expr) => {
+                let text = self.eval_expr(expr)?.to_string();
+                let number = self.file_number(number)?;
+                self.file(number)?.write_line(&text)?
+            }
+            Expr::InputFile(ref number, ref ids) => {
+                let number = self.file_number(number)?;
+                for id in ids {
+                    let field = self.file(number)?.read_field()?;
+                    self.store(id, Self::parse_input(id, &field))?;
+                }
+            }
+            Expr::LineInputFile(ref number, ref id) => {
+                let number = self.file_number(number)?;
+                let line = self.file(number)?.read_line()?;
+                self.store(id, Value::String(line))?
+            }
+            Expr::Field(ref number, ref fields) => self.field(number, fields)?,
+            Expr::Get { ref number, ref position, ref variable } => {
+                self.get(number, position.as_deref(), variable.as_deref())?
+            }
+            Expr::Put { ref number, ref position, ref variable } => {
+                self.put(number, position.as_deref(), variable.as_deref())?
+            }
+            Expr::LSet(ref id, ref expr) => self.justify(id, expr, false)?,
+            Expr::RSet(ref id, ref expr) => self.justify(id, expr, true)?,
+            Expr::Error(ref error) => return Err(ErrorKind::Syntax(error.message.clone())),
+            _ => { self.eval_expr(expr)?; },
         }
+        Ok(())
     }
-    
-    fn eval_expr(&mut self, expr: &Expr) -> Value {
+
+    fn eval_expr(&mut self, expr: &Expr) -> Result<Value, ErrorKind> {
         match expr {
-            Expr::Num(n) => Value::Integer(*n),
-            Expr::Str(s) => Value::String(s.to_string()),
+            Expr::Num(n) => Ok(Value::Integer(*n)),
+            Expr::Single(f) => Ok(Value::Single(*f)),
+            Expr::Float(f) => Ok(Value::Float(*f)),
+            Expr::Neg(operand) => self.negate(operand),
+            Expr::Str(s) => Ok(Value::String(s.to_string())),
             Expr::Bin(l, op, r) => self.bin_expr(l, op, r),
             Expr::Rel(l, op, r) => self.rel_expr(l, op, r),
             Expr::Call(func, args) => self.call_stmt(func, args),
-            Expr::Identifier(id) => self.variables.get(id).cloned().unwrap_or_else(|| panic!("Undefined variable: {}", id)),
+            Expr::Identifier(id) => self.variables.get(id).cloned().ok_or_else(|| ErrorKind::UndefinedVariable(id.clone())),
             _ => panic!("Unknown expression in interpreter"),
         }
     }
 
-    fn rel_expr(&mut self, left: &Box<Expr>, op: &Token, right: &Box<Expr>) -> Value {
-        let lval = self.eval_expr(left);
-        let rval = self.eval_expr(right);
+    fn rel_expr(&mut self, left: &Expr, op: &Token, right: &Expr) -> Result<Value, ErrorKind> {
+        let lval = self.eval_expr(left)?;
+        let rval = self.eval_expr(right)?;
+        let result = Self::relation(op, Self::compare(&lval, &rval)?);
 
+        Ok(self.truth_value(result))
+    }
+
+    fn truth_value(&self, b: bool) -> Value {
+        match self.dialect.truth {
+            Truth::Boolean => Value::Bool(b),
+            Truth::Classic => Value::Integer(if b { -1 } else { 0 }),
+        }
+    }
+
+    fn compare(lval: &Value, rval: &Value) -> Result<Ordering, ErrorKind> {
         match (lval, rval) {
-            (Value::Integer(lval), Value::Integer(rval)) => {
-                match op {
-                    Token::RelOp(op_str) => match op_str.as_str() {
-                        ">" => Value::Bool(lval > rval),
-                        "<" => Value::Bool(lval < rval),
-                        "<=" => Value::Bool(lval <= rval),
-                        ">=" => Value::Bool(lval >= rval),
-                        "=" => Value::Bool(lval == rval),
-                        "<>" => Value::Bool(lval != rval),
-                        _ => panic!(""),
-                    },
-                    _ => panic!("Invalid expression for relational comparison"),
-                }
+            (Value::Integer(lval), Value::Integer(rval)) => Ok(lval.cmp(rval)),
+            (Value::String(lval), Value::String(rval)) => Ok(lval.cmp(rval)),
+            (lval, rval) => match (lval.as_f64(), rval.as_f64()) {
+                (Some(lval), Some(rval)) => Ok(lval.partial_cmp(&rval).unwrap_or(Ordering::Less)),
+                _ => Err(ErrorKind::TypeMismatch("invalid types for comparison".to_string())),
+            },
+        }
+    }
+
+    fn relation(op: &Token, ordering: Ordering) -> bool {
+        match op {
+            Token::RelOp(op_str) => match op_str.as_str() {
+                ">" => ordering == Ordering::Greater,
+                "<" => ordering == Ordering::Less,
+                "<=" => ordering != Ordering::Greater,
+                ">=" => ordering != Ordering::Less,
+                "=" => ordering == Ordering::Equal,
+                "<>" | "!=" => ordering != Ordering::Equal,
+                _ => panic!("Unknown operator in relational expression"),
+            },
+            _ => panic!("Invalid expression for relational comparison"),
+        }
+    }
+
+    fn negate(&mut self, operand: &Expr) -> Result<Value, ErrorKind> {
+        match self.eval_expr(operand)? {
+            Value::Integer(n) => match (n.checked_neg(), self.dialect.overflow) {
+                (Some(n), _) => Ok(Value::Integer(n)),
+                (None, Overflow::Error) => Err(ErrorKind::Overflow),
+                (None, Overflow::Promote) => Ok(Value::Float(-(n as f64))),
             },
-            _ => panic!("Invalid type for relational comparison"),
+            Value::Single(f) => Ok(Value::Single(-f)),
+            Value::Float(f) => Ok(Value::Float(-f)),
+            _ => Err(ErrorKind::TypeMismatch("invalid type for negation".to_string())),
         }
     }
 
-    fn bin_expr(&mut self, left: &Box<Expr>, op: &Token, right: &Box<Expr>) -> Value {
-        let lval = self.eval_expr(left);
-        let rval = self.eval_expr(right);
+    fn bin_expr(&mut self, left: &Expr, op: &Token, right: &Expr) -> Result<Value, ErrorKind> {
+        let lval = self.eval_expr(left)?;
+        let rval = self.eval_expr(right)?;
+
+        let op_str = match op {
+            Token::BinOp(op_str) => op_str.as_str(),
+            _ => panic!("Invalid expression for arithmetic operation"),
+        };
 
         match (lval, rval) {
             (Value::Integer(lval), Value::Integer(rval)) => {
-                match op {
-                    Token::BinOp(op_str) => match op_str.as_str() {
-                        "+" => Value::Integer(lval + rval),
-                        "-" => Value::Integer(lval - rval),
-                        "*" => Value::Integer(lval * rval),
-                        "/" => {
-                            if rval == 0 {
-                                panic!("Division by zero")
-                            } else {
-                                Value::Integer(lval / rval)
-                            }
-                        },
-                        "%" => Value::Integer(lval % rval),
-                        _ => panic!("Unknown operator in binary expression")
-                    },
-                    _ => panic!(""),
+                let result = match op_str {
+                    "+" => lval.checked_add(rval),
+                    "-" => lval.checked_sub(rval),
+                    "*" => lval.checked_mul(rval),
+                    "/" | "%" if rval == 0 => return Err(ErrorKind::DivisionByZero),
+                    "/" => lval.checked_div(rval),
+                    "%" => lval.checked_rem(rval),
+                    _ => panic!("Unknown operator in binary expression")
+                };
+
+                match (result, self.dialect.overflow) {
+                    (Some(n), _) => Ok(Value::Integer(n)),
+                    (None, Overflow::Error) => Err(ErrorKind::Overflow),
+                    (None, Overflow::Promote) => Self::float_op(op_str, lval as f64, rval as f64).map(Value::Float),
                 }
             },
-            _ => panic!("Invalid types for arithmetic operation"),
+            (lval, rval) => {
+                // The result is single precision unless a double took part.
+                let double = matches!(lval, Value::Float(_)) || matches!(rval, Value::Float(_));
+                match (lval.as_f64(), rval.as_f64()) {
+                    (Some(lval), Some(rval)) if double => Self::float_op(op_str, lval, rval).map(Value::Float),
+                    (Some(lval), Some(rval)) => Self::float_op(op_str, lval, rval).map(|f| Value::Single(f as f32)),
+                    _ => Err(ErrorKind::TypeMismatch("invalid types for arithmetic operation".to_string())),
+                }
+            }
+        }
+    }
+
+    fn float_op(op_str: &str, lval: f64, rval: f64) -> Result<f64, ErrorKind> {
+        match op_str {
+            "+" => Ok(lval + rval),
+            "-" => Ok(lval - rval),
+            "*" => Ok(lval * rval),
+            "/" | "%" if rval == 0.0 => Err(ErrorKind::DivisionByZero),
+            "/" => Ok(lval / rval),
+            "%" => Ok(lval % rval),
+            _ => panic!("Unknown operator in binary expression")
+        }
+    }
+
+    fn eval_integer(&mut self, expr: &Expr, what: &str) -> Result<i64, ErrorKind> {
+        match self.eval_expr(expr)? {
+            Value::Integer(v) => Ok(v),
+            _ => Err(ErrorKind::TypeMismatch(format!("{} must be an integer", what))),
+        }
+    }
+
+    fn for_loop(&mut self, variable: &'a str, start: &Expr, end: &Expr, step: Option<&Expr>, body: &'a [Expr]) -> Result<(), ErrorKind> {
+        let start_val = self.eval_integer(start, "FOR start")?;
+        let end_val = self.eval_integer(end, "FOR end")?;
+        let step_val = match step {
+            Some(step_expr) => self.eval_integer(step_expr, "FOR step")?,
+            None => 1,
+        };
+
+        self.store(variable, Value::Integer(start_val))?;
+        if (step_val > 0 && start_val <= end_val) || (step_val < 0 && start_val >= end_val) {
+            let kind = FrameKind::For { variable, counter: start_val, end: end_val, step: step_val };
+            self.frames.push(Frame::new(body, kind, self.line));
+        }
+        Ok(())
+    }
+
+    fn select(&mut self, subject: &Expr, cases: &'a [Case], default: Option<&'a [Expr]>) -> Result<(), ErrorKind> {
+        let subject = self.eval_expr(subject)?;
+
+        for case in cases {
+            for test in &case.tests {
+                let matched = match test {
+                    CaseTest::Value(value) => {
+                        let value = self.eval_expr(value)?;
+                        Self::compare(&subject, &value)? == Ordering::Equal
+                    }
+                    CaseTest::Range(low, high) => {
+                        let low = self.eval_expr(low)?;
+                        let high = self.eval_expr(high)?;
+                        Self::compare(&subject, &low)? != Ordering::Less
+                            && Self::compare(&subject, &high)? != Ordering::Greater
+                    }
+                    CaseTest::Is(op, value) => {
+                        let value = self.eval_expr(value)?;
+                        Self::relation(op, Self::compare(&subject, &value)?)
+                    }
+                };
+
+                if matched {
+                    self.frames.push(Frame::new(&case.body, FrameKind::Select, self.line));
+                    return Ok(());
+                }
+            }
+        }
+
+        if let Some(default) = default {
+            self.frames.push(Frame::new(default, FrameKind::Select, self.line));
+        }
+        Ok(())
+    }
+
+    fn if_stmt(&mut self, cond: &Expr, cons: &'a [Expr]) -> Result<(), ErrorKind> {
+        let condition = self.eval_expr(cond)?;
+
+        let is_true = match (condition, self.dialect.truth) {
+            (Value::Bool(b), _) => b,
+            (Value::Integer(n), Truth::Classic) => n != 0,
+            (Value::Single(f), Truth::Classic) => f != 0.0,
+            (Value::Float(f), Truth::Classic) => f != 0.0,
+            (_, Truth::Classic) => return Err(ErrorKind::TypeMismatch("IF condition must be a number".to_string())),
+            (_, Truth::Boolean) => return Err(ErrorKind::TypeMismatch("IF condition must be a boolean".to_string())),
+        };
+
+        if is_true {
+            self.frames.push(Frame::new(cons, FrameKind::If, self.line));
+        }
+        Ok(())
+    }
+
+    fn resume(&mut self, at: ResumeAt) -> Result<(), ErrorKind> {
+        let trapped = self.trapped.take().ok_or(ErrorKind::ResumeWithoutError)?;
+        self.line = trapped.error.line;
+
+        match at {
+            ResumeAt::Same => {
+                self.frames = trapped.frames;
+                if !trapped.at_frame_end {
+                    self.frames.last_mut().expect("no block to resume").pc -= 1;
+                }
+            }
+            ResumeAt::Next => {
+                self.frames = trapped.frames;
+                if trapped.at_frame_end {
+                    self.frames.pop();
+                }
+            }
+            ResumeAt::Line(line) => self.jump(line)?,
+        }
+        Ok(())
+    }
+
+    fn raise(&mut self, code: &Expr) -> Result<(), ErrorKind> {
+        let code = self.eval_integer(code, "ERROR code")?;
+        if !(1..=255).contains(&code) {
+            return Err(ErrorKind::IllegalFunctionCall("ERROR code must be between 1 and 255".to_string()));
+        }
+        Err(ErrorKind::from_code(code))
+    }
+
+    fn assign_expr(&mut self, id: &str, expr: &Expr) -> Result<(), ErrorKind> {
+        if !self.variables.contains_key(id) {
+            return Err(ErrorKind::UndefinedVariable(id.to_string()));
+        }
+
+        let val = self.eval_expr(expr)?;
+        self.store(id, val)
+    }
+
+    fn var_dec(&mut self, id: &str, expr: &Expr) -> Result<(), ErrorKind> {
+        let val = self.eval_expr(expr)?;
+        self.store(id, val)
+    }
+
+    fn print(&mut self, expr: &Expr) -> Result<(), ErrorKind> {
+        let result = self.eval_expr(expr)?;
+        self.output.push_back(format!("{}\n", result));
+        Ok(())
+    }
+
+    /// Evaluates a file number, which must be between 1 and 255.
+    fn file_number(&mut self, expr: &Expr) -> Result<i64, ErrorKind> {
+        match self.eval_expr(expr)? {
+            Value::Integer(n) if (1..=255).contains(&n) => Ok(n),
+            Value::Integer(_) => Err(ErrorKind::BadFileNumber),
+            _ => Err(ErrorKind::TypeMismatch("file number must be an integer".to_string())),
         }
     }
 
-    fn for_loop(&mut self, variable: &String, start: &Box<Expr>, end: &Box<Expr>, step: Option<&Expr>, body: &[Expr]) {
-        let mut start_val = if let Value::Integer(v) = self.eval_expr(start) { v } else { panic!("For loop start must be an integer"); };
-        let end_val = if let Value::Integer(v) = self.eval_expr(end) { v } else { panic!("For loop end must be an integer"); };
-        let step_val = step.map_or(1, |step_expr| {
-            if let Value::Integer(v) = self.eval_expr(step_expr) {
-                v
-            } else {
-                panic!("For loop step must be an integer")
+    fn file(&mut self, number: i64) -> Result<&mut OpenFile, ErrorKind> {
+        self.files.get_mut(&number).ok_or(ErrorKind::BadFileNumber)
+    }
+
+    fn io_error(path: &str, error: io::Error) -> ErrorKind {
+        match error.kind() {
+            io::ErrorKind::NotFound => ErrorKind::FileNotFound(path.to_string()),
+            io::ErrorKind::PermissionDenied => ErrorKind::PermissionDenied(path.to_string()),
+            _ => ErrorKind::DeviceIo(error.to_string()),
+        }
+    }
+
+    fn open(&mut self, path: &Expr, mode: FileMode, number: &Expr, len: Option<&Expr>) -> Result<(), ErrorKind> {
+        let path = match self.eval_expr(path)? {
+            Value::String(path) => path,
+            _ => return Err(ErrorKind::TypeMismatch("file name must be a string".to_string())),
+        };
+        let number = self.file_number(number)?;
+        if self.files.contains_key(&number) {
+            return Err(ErrorKind::FileAlreadyOpen);
+        }
+
+        let file = match mode {
+            FileMode::Input => {
+                let mut stream = self.fs.open(&path, OpenMode::Read).map_err(|e| Self::io_error(&path, e))?;
+                let mut data = Vec::new();
+                stream.read_to_end(&mut data).map_err(|e| Self::io_error(&path, e))?;
+                OpenFile::Input { data, pos: 0 }
+            }
+            FileMode::Output => OpenFile::Output(self.fs.open(&path, OpenMode::Write).map_err(|e| Self::io_error(&path, e))?),
+            FileMode::Append => OpenFile::Output(self.fs.open(&path, OpenMode::Append).map_err(|e| Self::io_error(&path, e))?),
+            FileMode::Random => {
+                let record_len = match len {
+                    Some(len) => self.eval_integer(len, "record length")?,
+                    None => 128,
+                };
+                if !(1..=32767).contains(&record_len) {
+                    return Err(ErrorKind::IllegalFunctionCall("record length must be between 1 and 32767".to_string()));
+                }
+
+                OpenFile::Random {
+                    stream: self.fs.open(&path, OpenMode::ReadWrite).map_err(|e| Self::io_error(&path, e))?,
+                    record_len: record_len as usize,
+                    buffer: vec![0; record_len as usize],
+                    fields: Vec::new(),
+                    record: 0,
+                }
             }
+            FileMode::Binary => OpenFile::Binary(self.fs.open(&path, OpenMode::ReadWrite).map_err(|e| Self::io_error(&path, e))?),
+        };
+
+        self.files.insert(number, file);
+        Ok(())
+    }
+
+    /// Evaluates a record number of a random file or a byte position of a
+    /// binary file, both counted from 1.
+    fn record_number(&mut self, expr: &Expr) -> Result<u64, ErrorKind> {
+        match self.eval_integer(expr, "record number")? {
+            n if n >= 1 => Ok(n as u64),
+            _ => Err(ErrorKind::BadRecordNumber),
+        }
+    }
+
+    /// The type of a variable read or written by `GET` and `PUT`, with its
+    /// size in bytes. Strings take as many bytes as they currently hold.
+    fn record_var(&self, id: &str) -> Result<(VarType, usize), ErrorKind> {
+        let ty = VarType::of(id)
+            .ok_or_else(|| ErrorKind::TypeMismatch(format!("variable {} needs a type suffix for file access", id)))?;
+
+        let size = files::width(ty).unwrap_or_else(|| match self.variables.get(id) {
+            Some(Value::String(s)) => s.chars().count(),
+            _ => 0,
         });
+        Ok((ty, size))
+    }
 
-        self.variables.insert(variable.clone(), Value::Integer(start_val));
-        while (step_val > 0 && start_val <= end_val) || (step_val < 0 && start_val >= end_val) {
-            for stmt in body {
-                self.eval_stmt(stmt);
+    fn field(&mut self, number: &Expr, fields: &[(Expr, String)]) -> Result<(), ErrorKind> {
+        let number = self.file_number(number)?;
+
+        let mut layout = Vec::new();
+        let mut offset = 0;
+        for (width, id) in fields {
+            if VarType::of(id) != Some(VarType::String) {
+                return Err(ErrorKind::TypeMismatch(format!("FIELD variable {} must be a string", id)));
             }
 
-            start_val += step_val;
-            self.variables.insert(variable.clone(), Value::Integer(start_val));
+            let width = usize::try_from(self.eval_integer(width, "FIELD width")?)
+                .map_err(|_| ErrorKind::IllegalFunctionCall("FIELD width must not be negative".to_string()))?;
+            layout.push(Field { variable: id.clone(), offset, width });
+            offset += width;
+        }
+
+        let file = self.file(number)?;
+        file.set_fields(layout)?;
+        for (id, val) in file.field_values() {
+            self.store(&id, val)?;
         }
+        Ok(())
     }
 
-    fn if_stmt(&mut self, cond: &Box<Expr>, cons: &Box<Expr>) {
-        let condition = self.eval_expr(cond);
+    fn get(&mut self, number: &Expr, position: Option<&Expr>, variable: Option<&str>) -> Result<(), ErrorKind> {
+        let number = self.file_number(number)?;
+        let position = position.map(|p| self.record_number(p)).transpose()?;
+        let target = variable.map(|id| self.record_var(id).map(|var| (id, var))).transpose()?;
 
-        match condition {
-            Value::Bool(true) => {
-                self.eval_stmt(cons);
-            },
-            Value::Bool(false) => {},
-            _ => panic!("If statement must evaluate to a boolean")
+        let file = self.file(number)?;
+        let values = match (&file, target) {
+            (OpenFile::Random { .. }, target) => {
+                file.read_record(position)?;
+                match target {
+                    Some((id, (ty, size))) => vec![(id.to_string(), files::unpack(ty, file.record(size)?))],
+                    None => file.field_values(),
+                }
+            }
+            (OpenFile::Binary(_), Some((id, (ty, size)))) => {
+                vec![(id.to_string(), files::unpack(ty, &file.read_bytes(position, size)?))]
+            }
+            (OpenFile::Binary(_), None) => {
+                return Err(ErrorKind::IllegalFunctionCall("GET from a BINARY file needs a variable".to_string()));
+            }
+            _ => return Err(ErrorKind::BadFileMode),
+        };
+
+        for (id, val) in values {
+            self.store(&id, val)?;
         }
-        
+        Ok(())
     }
 
-    fn assign_expr(&mut self, id: &String, expr: &Box<Expr>) {
-        if !self.variables.contains_key(id) {
-            panic!("Cannot assign undefined variable");
+    fn put(&mut self, number: &Expr, position: Option<&Expr>, variable: Option<&str>) -> Result<(), ErrorKind> {
+        let number = self.file_number(number)?;
+        let position = position.map(|p| self.record_number(p)).transpose()?;
+        let data = match variable {
+            Some(id) => {
+                let (ty, _) = self.record_var(id)?;
+                let val = self.variables.get(id).cloned().ok_or_else(|| ErrorKind::UndefinedVariable(id.to_string()))?;
+                Some(files::pack(ty, id, val)?)
+            }
+            None => None,
+        };
+
+        let file = self.files.get_mut(&number).ok_or(ErrorKind::BadFileNumber)?;
+        match (&file, data) {
+            (OpenFile::Random { .. }, data) => {
+                match data {
+                    Some(data) => file.set_record(&data)?,
+                    None => file.fill_fields(&self.variables)?,
+                }
+                file.write_record(position)
+            }
+            (OpenFile::Binary(_), Some(data)) => file.write_bytes(position, &data),
+            (OpenFile::Binary(_), None) => {
+                Err(ErrorKind::IllegalFunctionCall("PUT to a BINARY file needs a variable".to_string()))
+            }
+            _ => Err(ErrorKind::BadFileMode),
         }
+    }
 
-        let val = self.eval_expr(expr);
-        self.variables.insert(id.to_string(), val);
+    /// `LSET` and `RSET`: store a string padded with spaces or cut to the
+    /// width of a `FIELD` variable, or to the variable's current length.
+    fn justify(&mut self, id: &str, expr: &Expr, right: bool) -> Result<(), ErrorKind> {
+        let text = match self.eval_expr(expr)? {
+            Value::String(s) => s,
+            _ => return Err(ErrorKind::TypeMismatch("LSET and RSET need a string".to_string())),
+        };
+
+        let width = self.files.values().find_map(|file| file.field_width(id))
+            .or_else(|| match self.variables.get(id) {
+                Some(Value::String(s)) => Some(s.chars().count()),
+                _ => None,
+            })
+            .unwrap_or(0);
+
+        let text: String = text.chars().take(width).collect();
+        let pad = " ".repeat(width - text.chars().count());
+        self.store(id, Value::String(if right { pad + &text } else { text + &pad }))
     }
 
-    fn var_dec(&mut self, id: &String, expr: &Box<Expr>) {
-        let val = match expr.as_ref() {
-            Expr::Call(func, args) => self.call_stmt(func, args),
-            _ => self.eval_expr(expr),
+    fn close(&mut self, numbers: &[Expr]) -> Result<(), ErrorKind> {
+        if numbers.is_empty() {
+            self.files.clear();
+        }
+
+        for number in numbers {
+            let number = self.file_number(number)?;
+            self.files.remove(&number);
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, ids: &[String]) -> Result<(), ErrorKind> {
+        for id in ids {
+            let val = match self.data.get(self.data_ptr) {
+                Some((_, val)) => val.clone(),
+                None => return Err(ErrorKind::OutOfData),
+            };
+            self.data_ptr += 1;
+
+            let val = match val {
+                Value::Integer(n) if VarType::of(id) == Some(VarType::String) => Value::String(n.to_string()),
+                Value::Single(f) if VarType::of(id) == Some(VarType::String) => Value::String(f.to_string()),
+                Value::Float(f) if VarType::of(id) == Some(VarType::String) => Value::String(f.to_string()),
+                val => val,
+            };
+            self.store(id, val)?;
+        }
+        Ok(())
+    }
+
+    fn restore(&mut self, line: Option<usize>) {
+        self.data_ptr = match line {
+            Some(line) => self.data.iter().position(|(l, _)| *l >= line).unwrap_or(self.data.len()),
+            None => 0,
         };
-        self.variables.insert(id.to_string(), val);
     }
-    
-    fn print(&mut self, expr: &Box<Expr>) {
-        let result = self.eval_expr(expr);
-        match result {
-            Value::Integer(n) => println!("{}", n),
-            Value::String(s) => println!("{}", s),
-            Value::Bool(b) => println!("{}", b),
-            Value::Float(f) => println!("{}", f),
-            _ => panic!("Invalid type for print")
+
+    fn randomize(&mut self, seed: Option<&Expr>) -> Result<(), ErrorKind> {
+        let seed = match seed.map(|seed| self.eval_expr(seed)).transpose()? {
+            Some(val) => Self::seed(val.as_f64().ok_or_else(|| ErrorKind::TypeMismatch("RANDOMIZE seed must be a number".to_string()))?),
+            None => self.fixed_seed.unwrap_or_else(|| {
+                SystemTime::now().duration_since(UNIX_EPOCH).map_or(DEFAULT_SEED, |d| d.as_nanos() as u64)
+            }),
+        };
+        self.rng = Rng::new(seed);
+        Ok(())
+    }
+
+    /// Turns the number given to `RANDOMIZE` or a negative `RND` into a
+    /// seed. Whole numbers seed alike whatever their type, so `RANDOMIZE 42`
+    /// and `RANDOMIZE 42.0` give the same sequence.
+    fn seed(n: f64) -> u64 {
+        if n.fract() == 0.0 && n >= i64::MIN as f64 && n < i64::MAX as f64 {
+            n as i64 as u64
+        } else {
+            n.to_bits()
+        }
+    }
+
+    fn arity(func_name: &str, args: &[Expr], min: usize, max: usize) -> Result<(), ErrorKind> {
+        if args.len() < min || args.len() > max {
+            return Err(ErrorKind::IllegalFunctionCall(format!("wrong number of arguments to {}", func_name)));
         }
+        Ok(())
     }
 
-    fn call_stmt(&mut self, func_name: &String, args: &Vec<Expr>) -> Value {
-        match func_name.as_str() {
+    fn call_stmt(&mut self, func_name: &str, args: &[Expr]) -> Result<Value, ErrorKind> {
+        match func_name {
             "SIN" => {
-                if args.len() != 1 {
-                    panic!("SIN function takes exactly one argument");
-                }
-    
-                let arg = &args[0];
-                let val = self.eval_expr(arg);
-    
-                match val {
+                Self::arity(func_name, args, 1, 1)?;
+                match self.eval_expr(&args[0])? {
                     Value::Integer(n) => {
                         let radians = (n as f64).to_radians();
                         let result = f64::sin(radians);
-                        return Value::Float(result)
+                        Ok(Value::Float(result))
                     }
-                    _ => panic!("SIN function expects an integer argument"),
+                    _ => Err(ErrorKind::TypeMismatch("SIN function expects an integer argument".to_string())),
+                }
+            }
+            "RND" => {
+                Self::arity(func_name, args, 0, 1)?;
+                let n = match args.first().map(|arg| self.eval_expr(arg)).transpose()? {
+                    Some(val) => val.as_f64().ok_or_else(|| ErrorKind::TypeMismatch("RND function expects a numeric argument".to_string()))?,
+                    None => 1.0,
+                };
+
+                if n < 0.0 {
+                    self.rng = Rng::new(Self::seed(n));
+                }
+                if n != 0.0 {
+                    self.last_rnd = self.rng.next_f64();
                 }
+                Ok(Value::Float(self.last_rnd))
             }
-            _ => panic!("Undefined function: {}", func_name),
+            "HEX$" | "OCT$" => {
+                Self::arity(func_name, args, 1, 1)?;
+                let n = match self.eval_expr(&args[0])? {
+                    Value::Integer(n) => n,
+                    Value::Single(f) => Value::round(f as f64)?,
+                    Value::Float(f) => Value::round(f)?,
+                    _ => return Err(ErrorKind::TypeMismatch(format!("{} function expects a numeric argument", func_name))),
+                };
+
+                if func_name == "HEX$" {
+                    Ok(Value::String(format!("{:X}", n)))
+                } else {
+                    Ok(Value::String(format!("{:o}", n)))
+                }
+            }
+            "ERR" => {
+                Self::arity(func_name, args, 0, 0)?;
+                Ok(Value::Integer(self.trapped.as_ref().map_or(0, |t| t.error.kind.code())))
+            }
+            "ERL" => {
+                Self::arity(func_name, args, 0, 0)?;
+                Ok(Value::Integer(self.trapped.as_ref().map_or(0, |t| t.error.line as i64)))
+            }
+            "LEN" => {
+                Self::arity(func_name, args, 1, 1)?;
+                match self.eval_expr(&args[0])? {
+                    Value::String(s) => Ok(Value::Integer(s.chars().count() as i64)),
+                    _ => Err(ErrorKind::TypeMismatch("LEN function expects a string argument".to_string())),
+                }
+            }
+            "MKI$" | "MKL$" | "MKS$" | "MKD$" | "CVI" | "CVL" | "CVS" | "CVD" => {
+                Self::arity(func_name, args, 1, 1)?;
+                let ty = match &func_name[2..3] {
+                    "I" => VarType::Integer,
+                    "L" => VarType::Long,
+                    "S" => VarType::Single,
+                    _ => VarType::Double,
+                };
+                let val = self.eval_expr(&args[0])?;
+
+                if func_name.starts_with("MK") {
+                    return Ok(Value::String(files::latin1(&files::pack(ty, func_name, val)?)));
+                }
+
+                let Value::String(s) = val else {
+                    return Err(ErrorKind::TypeMismatch(format!("{} function expects a string argument", func_name)));
+                };
+                let bytes = files::bytes(&s)?;
+                if Some(bytes.len()) < files::width(ty) {
+                    return Err(ErrorKind::IllegalFunctionCall(format!("{} needs a longer string", func_name)));
+                }
+                Ok(files::unpack(ty, &bytes))
+            }
+            "EOF" => {
+                Self::arity(func_name, args, 1, 1)?;
+                let number = self.file_number(&args[0])?;
+                let eof = self.file(number)?.eof()?;
+                Ok(self.truth_value(eof))
+            }
+            _ => self.call_native(func_name, args),
         }
     }
-    
 
-    fn input(&mut self, prompt: &String, out: &Box<Expr>) {
-        println!("{}", prompt);
+    fn call_native(&mut self, func_name: &str, args: &[Expr]) -> Result<Value, ErrorKind> {
+        let arity = match self.natives.get(func_name) {
+            Some(native) => native.params.len(),
+            None => return Err(ErrorKind::IllegalFunctionCall(format!("undefined function {}", func_name))),
+        };
+        Self::arity(func_name, args, arity, arity)?;
+
+        let mut values = Vec::new();
+        for arg in args {
+            values.push(self.eval_expr(arg)?);
+        }
 
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).expect("Failed to read line");
-        let input = input.trim();
+        let native = &self.natives[func_name];
+        let values = values.into_iter().zip(&native.params).enumerate()
+            .map(|(i, (val, ty))| ty.coerce(func_name, val).map_err(|error| match error {
+                ErrorKind::TypeMismatch(_) => ErrorKind::TypeMismatch(format!("argument {} of {}", i + 1, func_name)),
+                error => error,
+            }))
+            .collect::<Result<Vec<_>, _>>()?;
 
-        if let Expr::Identifier(id) = &**out {
-            if let Ok(num) = input.parse::<i64>() {
-                self.variables.insert(id.clone(), Value::Integer(num));
-            } else {
-                self.variables.insert(id.clone(), Value::String(input.to_string()));
-            }
+        (native.func)(&values)
+    }
+
+
+    /// Suspends until the host provides a line, then runs again to use it.
+    fn input(&mut self, prompt: &str, id: &str) -> Result<(), ErrorKind> {
+        let Some(input) = self.input.take() else {
+            self.awaiting_input = Some(prompt.to_string());
+            self.frames.last_mut().expect("INPUT outside of a block").pc -= 1;
+            // It runs, and counts as a statement, once the input arrives.
+            self.executed -= 1;
+            return Ok(());
+        };
+        self.store(id, Self::parse_input(id, input.trim()))
+    }
+
+    /// Converts text typed at `INPUT` or read by `INPUT #` for variable `id`.
+    fn parse_input(id: &str, input: &str) -> Value {
+        if VarType::of(id) == Some(VarType::String) {
+            Value::String(input.to_string())
+        } else if let Ok(num) = input.parse::<i64>() {
+            Value::Integer(num)
+        } else if let Ok(num) = input.parse::<f64>() {
+            Value::Float(num)
         } else {
-            panic!("Invalid expression for input");
+            Value::String(input.to_string())
         }
     }
-}
\ No newline at end of file
+}
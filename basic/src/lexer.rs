@@ -1,4 +1,18 @@
-use core::panic;
+use std::fmt;
+
+use crate::{dialect::Dialect, error::SyntaxError};
+
+pub const KEYWORDS: &[&str] = &[
+    "LET", "IF", "ELSEIF", "ELSE", "THEN", "PRINT", "AND", "OR", "NOT", "INPUT", "TRUE", "FALSE", "FOR", "TO", "STEP", "NEXT",
+    "RANDOMIZE", "DATA", "READ", "RESTORE", "SELECT", "CASE", "IS", "END",
+    "ON", "ERROR", "GOTO", "RESUME", "STOP", "OPEN", "CLOSE", "AS", "OUTPUT", "APPEND", "LINE",
+    "RANDOM", "BINARY", "FIELD", "GET", "PUT", "LSET", "RSET",
+];
+
+pub const BUILT_INS: &[&str] = &[
+    "ABS", "ATN", "COS", "EXP", "INT", "LOG", "RND", "SIN", "SQR", "TAN", "HEX$", "OCT$",
+    "ERR", "ERL", "EOF", "LEN", "MKI$", "MKL$", "MKS$", "MKD$", "CVI", "CVL", "CVS", "CVD",
+];
 
 #[derive(Debug, PartialEq)]
 pub enum Token {
@@ -9,27 +23,97 @@ pub enum Token {
     RelOp(String),
     Keyword(String),
     BuiltIn(String),
-    Punc(String)
+    Punc(String),
+    Newline,
+}
+
+/// Shows a token as it is written in the source.
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Str(s) => write!(f, "\"{}\"", s.replace('"', "\"\"")),
+            Token::Newline => write!(f, "end of line"),
+            Token::Number(text) | Token::Identifier(text) | Token::BinOp(text) | Token::RelOp(text)
+                | Token::Keyword(text) | Token::BuiltIn(text) | Token::Punc(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+/// Where a token starts in the source, counted from 1, and how many
+/// characters it covers.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Span {
+    pub line: usize,
+    pub col: usize,
+    pub len: usize,
+}
+
+/// Where a token began, for building its span once it ends.
+#[derive(Clone, Copy)]
+struct Start {
+    index: usize,
+    line: usize,
+    col: usize,
 }
 
 pub struct Lexer {
-    source: String,
-    current: usize
+    source: Vec<char>,
+    /// Index of the next character in `source`.
+    current: usize,
+    /// Line and column of the character at `current`, counted from 1.
+    line: usize,
+    col: usize,
+    dialect: Dialect,
+    spans: Vec<Span>,
+    errors: Vec<SyntaxError>,
 }
 
 impl Lexer {
     pub fn new(source: String) -> Lexer {
+        Lexer::with_dialect(source, Dialect::default())
+    }
+
+    pub fn with_dialect(source: String, dialect: Dialect) -> Lexer {
         Lexer {
-            source,
-            current: 0
+            source: source.chars().collect(),
+            current: 0,
+            line: 1,
+            col: 1,
+            dialect,
+            spans: Vec::new(),
+            errors: Vec::new(),
         }
     }
 
+    fn start(&self) -> Start {
+        Start { index: self.current, line: self.line, col: self.col }
+    }
+
+    fn span(&self, start: Start) -> Span {
+        Span { line: start.line, col: start.col, len: self.current - start.index }
+    }
+
+    fn error(&mut self, message: &str, start: Start) -> &mut SyntaxError {
+        let span = self.span(start);
+        self.errors.push(SyntaxError { message: message.to_string(), span, help: None });
+        self.errors.last_mut().expect("error was just recorded")
+    }
+
     fn current(&self) -> Option<char> {
-        return self.source.chars().nth(self.current);
+        self.source.get(self.current).copied()
+    }
+
+    fn next(&self) -> Option<char> {
+        self.source.get(self.current + 1).copied()
     }
 
     fn advance(&mut self) {
+        if self.current() == Some('\n') {
+            self.line += 1;
+            self.col = 1;
+        } else {
+            self.col += 1;
+        }
         self.current += 1;
     }
 
@@ -52,98 +136,261 @@ impl Lexer {
             }
         }
 
+        if self.is_exponent() {
+            num_str.extend(self.current().map(|c| c.to_ascii_uppercase()));
+            self.advance();
+
+            if let Some(sign) = self.current().filter(|&c| c == '+' || c == '-') {
+                num_str.push(sign);
+                self.advance();
+            }
+
+            while let Some(c) = self.current().filter(|c| c.is_ascii_digit()) {
+                num_str.push(c);
+                self.advance();
+            }
+        }
+
+        if let Some(suffix) = self.current().filter(|&c| "!#%&".contains(c)) {
+            if !self.operand_follows() || suffix == '!' || suffix == '#' {
+                num_str.push(suffix);
+                self.advance();
+            }
+        }
+
         Token::Number(num_str)
     }
 
+    /// Whether the character after the current one could start an operand,
+    /// in which case a `%` or `&` there is an operator rather than a type
+    /// suffix.
+    fn operand_follows(&self) -> bool {
+        self.next().is_some_and(|c| c.is_alphanumeric() || c == '(' || c == '.')
+    }
+
+    /// `E` marks a single-precision exponent and `D` a double-precision one;
+    /// both are only treated as exponents when followed by digits. The
+    /// letter is kept, uppercased, in the number's text.
+    fn is_exponent(&self) -> bool {
+        let mut rest = self.source[self.current..].iter().copied();
+
+        if !matches!(rest.next(), Some('E' | 'e' | 'D' | 'd')) {
+            return false;
+        }
+
+        match rest.next() {
+            Some('+' | '-') => rest.next().is_some_and(|c| c.is_ascii_digit()),
+            Some(c) => c.is_ascii_digit(),
+            None => false,
+        }
+    }
+
+    pub fn radix_number(&mut self) -> Token {
+        let start = self.start();
+        self.advance();
+
+        let radix = match self.current().map(|c| c.to_ascii_uppercase()) {
+            Some('H') => 16,
+            Some('O') => 8,
+            _ => 2,
+        };
+        self.advance();
+
+        let mut digits = String::new();
+        while let Some(c) = self.current().filter(|c| c.is_digit(radix)) {
+            digits.push(c);
+            self.advance();
+        }
+
+        if digits.is_empty() {
+            self.error("Expected digits in literal", start);
+            return Token::Number("0".to_string());
+        }
+
+        match u64::from_str_radix(&digits, radix) {
+            Ok(value) => Token::Number((value as i64).to_string()),
+            Err(_) => {
+                self.error("Overflow in literal", start);
+                Token::Number("0".to_string())
+            }
+        }
+    }
+
+    fn is_radix_prefix(&self) -> bool {
+        matches!(self.next().map(|c| c.to_ascii_uppercase()), Some('H' | 'O' | 'B'))
+    }
+
     pub fn string(&mut self) -> Token {
+        let start = self.start();
         self.advance();
-        let str: String = self.source[self.current..]
-            .chars().take_while(|&c| c != '\"').collect();
 
-        self.current += str.len() + 1;
-        return Token::Str(str);
+        let mut str = String::new();
+        loop {
+            match self.current() {
+                Some('\"') => {
+                    self.advance();
+                    if self.current() != Some('\"') {
+                        break;
+                    }
+                    str.push('\"');
+                    self.advance();
+                }
+                Some('\\') if self.dialect.string_escapes => {
+                    self.advance();
+                    match self.current() {
+                        Some('n') => str.push('\n'),
+                        Some('t') => str.push('\t'),
+                        Some('\\') => str.push('\\'),
+                        Some('\"') => str.push('\"'),
+                        _ => {
+                            str.push('\\');
+                            continue;
+                        }
+                    }
+                    self.advance();
+                }
+                Some('\n') | None => {
+                    self.error("Unterminated string literal", start).help =
+                        Some("add a closing `\"` before the end of the line".to_string());
+                    break;
+                }
+                Some(c) => {
+                    str.push(c);
+                    self.advance();
+                }
+            }
+        }
+
+        Token::Str(str)
     }
 
-    pub fn identifier(&mut self) -> Token {
-        let str: String = self.source[self.current..]
-            .chars().take_while(|&c| c.is_alphabetic() || c == '_').collect();
-        
-        self.current += str.len() + 1;
+    pub fn comment(&mut self) {
+        while let Some(c) = self.current() {
+            if c == '\n' {
+                break;
+            }
+            self.advance();
+        }
+    }
+
+    fn is_rem(&self) -> bool {
+        let word: String = self.source[self.current..].iter()
+            .take_while(|&&c| c.is_alphanumeric() || c == '_').collect();
+
+        word.eq_ignore_ascii_case("REM")
+    }
 
-        let keywords = [
-            "LET", "IF", "ELSEIF", "ELSE", "THEN", "PRINT", "AND", "OR", "NOT", "INPUT", "TRUE", "FALSE", "FOR", "TO", "STEP", "NEXT",
-        ];
+    pub fn identifier(&mut self) -> Token {
+        let mut str = String::new();
+        while let Some(c) = self.current().filter(|&c| c.is_alphanumeric() || c == '_') {
+            str.push(c);
+            self.advance();
+        }
 
-        let built_ins = [
-            "ABS", "ATN", "COS", "EXP", "INT", "LOG", "RND", "SIN", "SQR", "TAN"
-        ];
+        // Only `$` is always a sigil: `x%3` is a modulo, `a!=5` a comparison
+        // and `#1` a file number.
+        if let Some(sigil) = self.current().filter(|&c| "$%!#&".contains(c)) {
+            let not_equal = sigil == '!' && self.next() == Some('=');
+            if sigil == '$' || !(self.operand_follows() || not_equal) {
+                str.push(sigil);
+                self.advance();
+            }
+        }
 
-        if keywords.contains(&str.to_uppercase().as_str()) {
+        if KEYWORDS.contains(&str.to_uppercase().as_str()) {
             return Token::Keyword(str.to_uppercase());
         }
 
-        if built_ins.contains(&str.to_uppercase().as_str()) {
-            self.current -= 1;
+        if BUILT_INS.contains(&str.to_uppercase().as_str()) {
             return Token::BuiltIn(str.to_uppercase());
         }
 
-        return Token::Identifier(str);
+        Token::Identifier(str)
     }
 
-    pub fn operator(&mut self) -> Token {
+    pub fn operator(&mut self) -> Option<Token> {
+        let start = self.start();
         let single = self.current().expect("ERROR tokenizing operator").to_string();
     
-        if "(),".contains(single.as_str()) {
+        if "(),:#".contains(single.as_str()) {
             self.advance();
-            return Token::Punc(single);
+            return Some(Token::Punc(single));
         }
     
         let ops = ["+", "-", "*", "/", "%", "=", "<", "<=", ">", ">=", "!=", "<>"];
         let mut double = single.clone();
     
-        if let Some(next) = self.source.chars().nth(self.current + 1) {
-            double.push(next);
-        }
+        double.extend(self.next());
     
         if ops.contains(&double.as_str()) {
-            self.current += 2;
-            if "><=!".contains(&single.as_str()) {
-                return Token::RelOp(double);
+            self.advance();
+            self.advance();
+            if "><=!".contains(single.as_str()) {
+                return Some(Token::RelOp(double));
             }
-            return Token::BinOp(double);
+            return Some(Token::BinOp(double));
         }
     
         if ops.contains(&single.as_str()) {
             self.advance();
-            if "><=!".contains(&single.as_str()) {
-                return Token::RelOp(single);
+            if "><=!".contains(single.as_str()) {
+                return Some(Token::RelOp(single));
             }
-            return Token::BinOp(single);
+            return Some(Token::BinOp(single));
         }
     
-        panic!("Unexpected operator");
+        self.advance();
+        self.error("Unexpected operator", start);
+        None
     }
-    
 
-    pub fn tokenize(&mut self) -> Vec<Token> {
+    /// Tokenizes the source, recording malformed tokens in `errors` and
+    /// carrying on after them. Malformed literals still produce a token, so
+    /// the rest of their line parses.
+    pub fn scan(&mut self) -> Vec<Token> {
         let mut tokens = Vec::new();
 
-        while let Some(c) = self.source.chars().nth(self.current) {
-            if c.is_whitespace() {
+        while let Some(c) = self.current() {
+            let start = self.start();
+            let token = if c == '\n' {
                 self.advance();
-            } else if c.is_ascii_digit() {
-                tokens.push(self.numeric());
+                Some(Token::Newline)
+            } else if c.is_whitespace() {
+                self.advance();
+                None
+            } else if c.is_ascii_digit() || (c == '.' && self.next().is_some_and(|c| c.is_ascii_digit())) {
+                Some(self.numeric())
+            } else if c == '&' && self.is_radix_prefix() {
+                Some(self.radix_number())
             } else if c == '\"' {
-                tokens.push(self.string());
+                Some(self.string())
+            } else if c == '\'' || (c.is_alphabetic() && self.is_rem()) {
+                self.comment();
+                None
             } else if c.is_alphabetic() {
-                tokens.push(self.identifier());
-            } else if "+-*/=<>^%()".contains(c) {
-                tokens.push(self.operator());
+                Some(self.identifier())
+            } else if "+-*/=<>^%(),:#!".contains(c) {
+                self.operator()
             } else { 
                 self. advance();
+                None
+            };
+
+            if let Some(token) = token {
+                tokens.push(token);
+                self.spans.push(self.span(start));
             }
         }
 
-        return tokens
+        tokens
+    }
+
+    /// Where each token returned by `scan` came from.
+    pub fn spans(&self) -> &[Span] {
+        &self.spans
+    }
+
+    pub fn errors(&self) -> &[SyntaxError] {
+        &self.errors
     }
 }
\ No newline at end of file
@@ -0,0 +1,14 @@
+pub mod dialect;
+pub mod error;
+pub mod lexer;
+pub mod parser;
+pub mod interpreter;
+pub mod rng;
+pub mod fs;
+pub mod limits;
+pub mod debugger;
+pub mod diagnostic;
+pub mod dap;
+pub mod lsp;
+mod protocol;
+mod files;
@@ -0,0 +1,36 @@
+use std::{fmt, time::Duration};
+
+/// Resource limits for running untrusted programs. Every limit is off by
+/// default; the time and statement limits apply to each call of `interpret`
+/// or `run_immediate`, including any `cont` of a program it stopped.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Limits {
+    /// Most statements to execute.
+    pub statements: Option<u64>,
+    /// Longest time to run for.
+    pub time: Option<Duration>,
+    /// Most bytes of string data to hold in variables at once.
+    pub memory: Option<usize>,
+    /// Deepest nesting of `FOR`, `IF` and `SELECT CASE` blocks.
+    pub depth: Option<usize>,
+}
+
+/// Which of the `Limits` a program ran into.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Limit {
+    Statements,
+    Time,
+    Memory,
+    Depth,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Limit::Statements => write!(f, "statement"),
+            Limit::Time => write!(f, "time"),
+            Limit::Memory => write!(f, "memory"),
+            Limit::Depth => write!(f, "nesting depth"),
+        }
+    }
+}
@@ -0,0 +1,331 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+};
+
+use serde_json::{json, Value as Json};
+
+use crate::{
+    dialect::Dialect,
+    error::SyntaxError,
+    lexer::{Lexer, Span, Token, BUILT_INS, KEYWORDS},
+    parser::Parser,
+    protocol,
+};
+
+/// Signatures and descriptions of the built-in functions, shown on hover
+/// and in completions.
+const SIGNATURES: &[(&str, &str, &str)] = &[
+    ("ABS", "ABS(x)", "Absolute value of x."),
+    ("ATN", "ATN(x)", "Arctangent of x, in radians."),
+    ("COS", "COS(x)", "Cosine of an angle of x radians."),
+    ("EXP", "EXP(x)", "e raised to the power of x."),
+    ("INT", "INT(x)", "Largest integer not greater than x."),
+    ("LOG", "LOG(x)", "Natural logarithm of x."),
+    ("RND", "RND[(n)]", "Next random number from 0 up to 1. RND(0) repeats the last number and a negative n reseeds the generator."),
+    ("SIN", "SIN(degrees)", "Sine of an angle given as a whole number of degrees."),
+    ("SQR", "SQR(x)", "Square root of x."),
+    ("TAN", "TAN(x)", "Tangent of an angle of x radians."),
+    ("HEX$", "HEX$(n)", "Hexadecimal digits of n."),
+    ("OCT$", "OCT$(n)", "Octal digits of n."),
+    ("ERR", "ERR", "Code of the error being handled by an ON ERROR handler."),
+    ("ERL", "ERL", "Line of the error being handled by an ON ERROR handler."),
+    ("EOF", "EOF(file)", "Whether an open file has no more input."),
+    ("LEN", "LEN(s$)", "Number of characters in s$."),
+    ("MKI$", "MKI$(n)", "Packs an integer into a 2-byte string for a record field."),
+    ("MKL$", "MKL$(n)", "Packs a long integer into a 4-byte string for a record field."),
+    ("MKS$", "MKS$(x)", "Packs a single-precision number into a 4-byte string for a record field."),
+    ("MKD$", "MKD$(x)", "Packs a double-precision number into an 8-byte string for a record field."),
+    ("CVI", "CVI(s$)", "Unpacks an integer packed by MKI$."),
+    ("CVL", "CVL(s$)", "Unpacks a long integer packed by MKL$."),
+    ("CVS", "CVS(s$)", "Unpacks a single-precision number packed by MKS$."),
+    ("CVD", "CVD(s$)", "Unpacks a double-precision number packed by MKD$."),
+];
+
+/// Statements whose line number operand jumps to, or reads from, a line.
+const LINE_TARGETS: &[&str] = &["GOTO", "RESTORE", "RESUME"];
+
+// Kinds from the LSP specification.
+const COMPLETION_FUNCTION: i64 = 3;
+const COMPLETION_VARIABLE: i64 = 6;
+const COMPLETION_KEYWORD: i64 = 14;
+const SYMBOL_VARIABLE: i64 = 13;
+const SEVERITY_ERROR: i64 = 1;
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// Serves the Language Server Protocol for BASIC sources, reading messages
+/// from `input` and writing to `output` until the client exits.
+///
+/// The dialect has no `SUB` procedures, so definitions cover variables, at
+/// the first statement that assigns them, and the line numbers used by
+/// `ON ERROR GOTO`, `RESUME` and `RESTORE`.
+pub fn serve<R: BufRead, W: Write>(mut input: R, output: W, dialect: Dialect) -> io::Result<()> {
+    let mut server = Server { output, dialect, documents: HashMap::new() };
+
+    while let Some(message) = protocol::read_message(&mut input)? {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+
+        if message.get("id").is_none() {
+            if method == "exit" {
+                break;
+            }
+            server.notification(method, params)?;
+            continue;
+        }
+
+        let response = match server.request(method, params) {
+            Some(result) => json!({ "jsonrpc": "2.0", "id": message["id"], "result": result }),
+            None => json!({
+                "jsonrpc": "2.0",
+                "id": message["id"],
+                "error": { "code": METHOD_NOT_FOUND, "message": format!("Unsupported method {}", method) },
+            }),
+        };
+        protocol::write_message(&mut server.output, &response)?;
+    }
+
+    Ok(())
+}
+
+/// The tokens and syntax errors of an open source file.
+struct Document {
+    lines: usize,
+    tokens: Vec<Token>,
+    spans: Vec<Span>,
+    errors: Vec<SyntaxError>,
+}
+
+impl Document {
+    fn analyze(text: &str, dialect: Dialect) -> Document {
+        let mut lexer = Lexer::with_dialect(text.to_string(), dialect);
+        let tokens = lexer.scan();
+
+        let mut parser = Parser::with_spans(&tokens, lexer.spans());
+        parser.parse();
+        let mut errors: Vec<_> = lexer.errors().iter().chain(parser.errors()).cloned().collect();
+        errors.sort_by_key(|error| (error.span.line, error.span.col));
+
+        Document { lines: text.lines().count(), spans: lexer.spans().to_vec(), tokens, errors }
+    }
+
+    /// The index of the token under an LSP position.
+    fn token_at(&self, position: &Json) -> Option<usize> {
+        let line = position["line"].as_u64()? as usize + 1;
+        let col = position["character"].as_u64()? as usize + 1;
+
+        self.spans.iter().position(|span| span.line == line && span.col <= col && col <= span.col + span.len)
+            .filter(|&i| self.tokens[i] != Token::Newline)
+    }
+
+    /// Each variable with the index of the token that first assigns it, in
+    /// source order. Variables that are never assigned are defined where
+    /// they are first used.
+    fn definitions(&self) -> Vec<(&str, usize)> {
+        let mut assigned: Vec<(&str, usize)> = Vec::new();
+        let mut used: Vec<(&str, usize)> = Vec::new();
+        let mut statement = None;
+        let mut at_start = true;
+
+        for (i, token) in self.tokens.iter().enumerate() {
+            match token {
+                Token::Newline => {
+                    statement = None;
+                    at_start = true;
+                    continue;
+                }
+                Token::Punc(punc) if punc == ":" => {
+                    statement = None;
+                    at_start = true;
+                    continue;
+                }
+                Token::Keyword(keyword) if keyword == "THEN" => {
+                    at_start = true;
+                    continue;
+                }
+                Token::Keyword(keyword) if at_start => statement = Some(keyword.as_str()),
+                Token::Identifier(name) if !self.rejected(i) => {
+                    let previous = i.checked_sub(1).map(|i| &self.tokens[i]);
+                    let after_keyword = |words: &[&str]| matches!(previous, Some(Token::Keyword(k)) if words.contains(&k.as_str()));
+
+                    let written = (at_start && self.tokens.get(i + 1) == Some(&Token::RelOp("=".to_string())))
+                        || after_keyword(&["LET", "FOR", "LSET", "RSET"])
+                        || (statement == Some("FIELD") && after_keyword(&["AS"]))
+                        || matches!(statement, Some("INPUT" | "READ" | "LINE" | "GET"));
+
+                    let list = if written { &mut assigned } else { &mut used };
+                    if !list.iter().any(|(defined, _)| defined == name) {
+                        list.push((name, i));
+                    }
+                }
+                _ => {}
+            }
+            at_start = false;
+        }
+
+        used.retain(|(name, _)| !assigned.iter().any(|(defined, _)| defined == name));
+        assigned.extend(used);
+        assigned.sort_by_key(|&(_, i)| i);
+        assigned
+    }
+
+    /// Whether a syntax error points at the token, as it does at a
+    /// misspelled keyword, which is then no variable.
+    fn rejected(&self, i: usize) -> bool {
+        let span = &self.spans[i];
+        self.errors.iter().any(|error| error.span.line == span.line && error.span.col == span.col)
+    }
+}
+
+fn position(line: usize, col: usize) -> Json {
+    json!({ "line": line.saturating_sub(1), "character": col.saturating_sub(1) })
+}
+
+fn range(span: &Span) -> Json {
+    json!({ "start": position(span.line, span.col), "end": position(span.line, span.col + span.len) })
+}
+
+fn signature(name: &str) -> Option<(&'static str, &'static str)> {
+    SIGNATURES.iter().find(|(built_in, _, _)| *built_in == name).map(|&(_, signature, doc)| (signature, doc))
+}
+
+struct Server<W: Write> {
+    output: W,
+    dialect: Dialect,
+    documents: HashMap<String, Document>,
+}
+
+impl<W: Write> Server<W> {
+    fn notification(&mut self, method: &str, params: &Json) -> io::Result<()> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default().to_string();
+
+        let text = match method {
+            "textDocument/didOpen" => params["textDocument"]["text"].as_str(),
+            // Changes are requested in full, so the last one is the whole text.
+            "textDocument/didChange" => params["contentChanges"].as_array()
+                .and_then(|changes| changes.last())
+                .and_then(|change| change["text"].as_str()),
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                return self.publish(&uri, &[]);
+            }
+            _ => None,
+        };
+        let Some(text) = text else {
+            return Ok(());
+        };
+
+        let document = Document::analyze(text, self.dialect);
+        let errors = document.errors.clone();
+        self.documents.insert(uri.clone(), document);
+        self.publish(&uri, &errors)
+    }
+
+    fn publish(&mut self, uri: &str, errors: &[SyntaxError]) -> io::Result<()> {
+        let diagnostics: Vec<_> = errors.iter()
+            .map(|error| json!({
+                "range": range(&error.span),
+                "severity": SEVERITY_ERROR,
+                "source": "basic",
+                "message": match &error.help {
+                    Some(help) => format!("{}\nhelp: {}", error.message, help),
+                    None => error.message.clone(),
+                },
+            }))
+            .collect();
+
+        protocol::write_message(&mut self.output, &json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        }))
+    }
+
+    /// Answers a request, or returns `None` for unsupported methods.
+    fn request(&mut self, method: &str, params: &Json) -> Option<Json> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let document = self.documents.get(uri);
+
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "completionProvider": {},
+                    "definitionProvider": true,
+                    "hoverProvider": true,
+                    "documentSymbolProvider": true,
+                },
+                "serverInfo": { "name": "basic" },
+            }),
+            "shutdown" => Json::Null,
+            "textDocument/completion" => {
+                let keywords = KEYWORDS.iter().map(|keyword| json!({ "label": keyword, "kind": COMPLETION_KEYWORD }));
+                let built_ins = BUILT_INS.iter().map(|name| {
+                    let (signature, doc) = signature(name).unwrap_or_default();
+                    json!({ "label": name, "kind": COMPLETION_FUNCTION, "detail": signature, "documentation": doc })
+                });
+                let variables = document.into_iter().flat_map(Document::definitions)
+                    .map(|(name, _)| json!({ "label": name, "kind": COMPLETION_VARIABLE }));
+
+                Json::Array(keywords.chain(built_ins).chain(variables).collect())
+            }
+            "textDocument/definition" => {
+                let definition = document.and_then(|document| {
+                    let i = document.token_at(&params["position"])?;
+
+                    match &document.tokens[i] {
+                        Token::Identifier(name) => {
+                            let (_, at) = document.definitions().into_iter().find(|(defined, _)| defined == name)?;
+                            Some(range(&document.spans[at]))
+                        }
+                        Token::Number(number) => {
+                            let target = i.checked_sub(1).map(|i| &document.tokens[i]);
+                            if !matches!(target, Some(Token::Keyword(k)) if LINE_TARGETS.contains(&k.as_str())) {
+                                return None;
+                            }
+
+                            let line: usize = number.parse().ok()?;
+                            (1..=document.lines).contains(&line)
+                                .then(|| range(&Span { line, col: 1, len: 0 }))
+                        }
+                        _ => None,
+                    }
+                });
+
+                match definition {
+                    Some(range) => json!({ "uri": uri, "range": range }),
+                    None => Json::Null,
+                }
+            }
+            "textDocument/hover" => {
+                let hover = document.and_then(|document| {
+                    let i = document.token_at(&params["position"])?;
+                    let Token::BuiltIn(name) = &document.tokens[i] else {
+                        return None;
+                    };
+
+                    let (signature, doc) = signature(name)?;
+                    Some(json!({
+                        "contents": { "kind": "markdown", "value": format!("```basic\n{}\n```\n{}", signature, doc) },
+                        "range": range(&document.spans[i]),
+                    }))
+                });
+                hover.unwrap_or(Json::Null)
+            }
+            "textDocument/documentSymbol" => {
+                let symbols = document.into_iter().flat_map(|document| {
+                    document.definitions().into_iter().map(|(name, i)| json!({
+                        "name": name,
+                        "kind": SYMBOL_VARIABLE,
+                        "range": range(&document.spans[i]),
+                        "selectionRange": range(&document.spans[i]),
+                    }))
+                });
+                Json::Array(symbols.collect())
+            }
+            _ => return None,
+        };
+
+        Some(result)
+    }
+}
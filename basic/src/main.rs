@@ -1,22 +1,332 @@
-mod lexer;
-mod parser;
-mod interpreter;
+use std::io::{self, Write};
+
+use basic::{
+    debugger::{DebugHook, Debugger, Pause, Resume},
+    diagnostic::{self, Format},
+    dialect::{Dialect, Overflow, Truth},
+    error::{RuntimeError, SyntaxError},
+    interpreter::{Completion, Interpreter},
+    parser::{self, Expr},
+};
+use typed_arena::Arena;
 
 fn main() {
-    let source: String = std::fs::read_to_string("example/code.txt").expect("error reading file");
-    
-    let mut lexer = lexer::Lexer::new(source);
-    let tokens = lexer.tokenize();
-    // for token in &tokens {
-    //     println!("{:?}", token);
-    // }
-
-    let mut parser = parser::Parser::new(&tokens);
-    let exprs = parser.parse();
-    for expr in &exprs {
-        println!("{:?}", expr)
-    }
-
-    let mut interpreter = interpreter::Interpreter::new(&exprs);
-    interpreter.interpret();
-}
\ No newline at end of file
+    let mut dialect = Dialect::default();
+    let mut format = Format::default();
+    let mut dump_ast = false;
+    let mut args = Vec::new();
+
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--escapes" => dialect.string_escapes = true,
+            "--overflow=error" => dialect.overflow = Overflow::Error,
+            "--overflow=promote" => dialect.overflow = Overflow::Promote,
+            "--truth=boolean" => dialect.truth = Truth::Boolean,
+            "--truth=classic" => dialect.truth = Truth::Classic,
+            "--format=plain" => format = Format::Plain,
+            "--format=json" => format = Format::Json,
+            "--dump-ast" => dump_ast = true,
+            _ => args.push(arg),
+        }
+    }
+
+    match args.first().map(String::as_str) {
+        Some("repl") => repl(dialect),
+        Some("dap") => {
+            let stdin = io::BufReader::new(io::stdin());
+            basic::dap::serve(stdin, io::stdout(), dialect).expect("error talking to the debug client");
+        }
+        Some("lsp") => {
+            let stdin = io::BufReader::new(io::stdin());
+            basic::lsp::serve(stdin, io::stdout(), dialect).expect("error talking to the editor");
+        }
+        Some("check") if args.len() > 1 => {
+            if !check(&args[1..], dialect, format) {
+                std::process::exit(1);
+            }
+        }
+        Some("check") => {
+            eprintln!("usage: basic check FILE...");
+            std::process::exit(2);
+        }
+        Some("debug") => match args.get(1) {
+            Some(path) => debug(path, dialect),
+            None => {
+                eprintln!("usage: basic debug FILE");
+                std::process::exit(2);
+            }
+        },
+        Some(path) => run(path, dialect, format, dump_ast),
+        None => run("example/code.txt", dialect, format, dump_ast),
+    }
+}
+
+/// Parses a program, printing its syntax errors if it has any. `path` names
+/// the source in the errors.
+fn parse(path: &str, source: &str, dialect: Dialect, format: Format) -> Option<Vec<Expr>> {
+    let (exprs, errors) = parser::parse_program(source, dialect);
+    print_syntax_errors(&errors, path, source, format);
+
+    errors.is_empty().then_some(exprs)
+}
+
+fn print_syntax_errors(errors: &[SyntaxError], path: &str, source: &str, format: Format) {
+    for error in errors {
+        let rendered = diagnostic::render(error, path, source, format);
+        match format {
+            Format::Plain => eprintln!("{}\n", rendered),
+            Format::Json => eprintln!("{}", rendered),
+        }
+    }
+}
+
+fn run(path: &str, dialect: Dialect, format: Format, dump_ast: bool) {
+    let source: String = std::fs::read_to_string(path).expect("error reading file");
+
+    let Some(exprs) = parse(path, &source, dialect, format) else {
+        std::process::exit(1);
+    };
+    if dump_ast {
+        for expr in &exprs {
+            println!("{:?}", expr)
+        }
+    }
+
+    let mut interpreter = Interpreter::with_dialect(&exprs, dialect);
+    break_on_interrupt(&interpreter);
+    if !report(interpreter.interpret(), Some((path, &source)), format) {
+        std::process::exit(1);
+    }
+}
+
+/// Makes Ctrl+C break the running program instead of killing the process.
+fn break_on_interrupt(interpreter: &Interpreter) {
+    let token = interpreter.cancel_token();
+    ctrlc::set_handler(move || token.cancel()).expect("error installing Ctrl+C handler");
+}
+
+/// Prints how a run ended and returns whether it ended without an error.
+/// Errors are rendered against `file`, a path and its contents, when the
+/// program came from one.
+fn report(result: Result<Completion, RuntimeError>, file: Option<(&str, &str)>, format: Format) -> bool {
+    match result {
+        Ok(Completion::Finished) => true,
+        Ok(Completion::Break(line)) => {
+            println!("Break in line {}", line);
+            true
+        }
+        Err(error) => {
+            match file {
+                Some((path, source)) => eprintln!("{}", diagnostic::render(&error, path, source, format)),
+                None => eprintln!("{}", error),
+            }
+            false
+        }
+    }
+}
+
+/// Reports the syntax errors of each file without running any of them.
+/// Returns whether every file parsed.
+fn check(paths: &[String], dialect: Dialect, format: Format) -> bool {
+    let mut ok = true;
+
+    for path in paths {
+        let source = match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(error) => {
+                eprintln!("{}: {}", path, error);
+                ok = false;
+                continue;
+            }
+        };
+
+        let (_, errors) = parser::parse_program(&source, dialect);
+        print_syntax_errors(&errors, path, &source, format);
+        ok &= errors.is_empty();
+    }
+
+    ok
+}
+
+fn repl(dialect: Dialect) {
+    // Every parsed line and program lives as long as the session, so that a
+    // program paused by `STOP` can keep pointing into them.
+    let programs: Arena<Vec<Expr>> = Arena::new();
+    let mut interpreter = Interpreter::with_dialect(&[], dialect);
+    break_on_interrupt(&interpreter);
+
+    loop {
+        print!("> ");
+        io::stdout().flush().expect("error writing prompt");
+
+        let mut line = String::new();
+        if io::stdin().read_line(&mut line).expect("error reading input") == 0 {
+            break;
+        }
+
+        let command = line.trim();
+        let upper = command.to_uppercase();
+
+        let result = if upper.is_empty() {
+            continue;
+        } else if upper == "EXIT" || upper == "QUIT" {
+            break;
+        } else if upper == "CONT" {
+            interpreter.cont()
+        } else if let Some(path) = upper.strip_prefix("RUN ").map(|_| command[4..].trim().trim_matches('"')) {
+            let source = match std::fs::read_to_string(path) {
+                Ok(source) => source,
+                Err(error) => {
+                    eprintln!("{}: {}", path, error);
+                    continue;
+                }
+            };
+            let Some(exprs) = parse(path, &source, dialect, Format::Plain) else { continue };
+
+            interpreter.load(programs.alloc(exprs));
+            interpreter.interpret()
+        } else {
+            let Some(exprs) = parse("<input>", command, dialect, Format::Plain) else { continue };
+            interpreter.run_immediate(programs.alloc(exprs))
+        };
+
+        report(result, None, Format::Plain);
+    }
+}
+
+fn debug(path: &str, dialect: Dialect) {
+    let source: String = std::fs::read_to_string(path).expect("error reading file");
+    let Some(exprs) = parse(path, &source, dialect, Format::Plain) else {
+        std::process::exit(1);
+    };
+
+    let mut interpreter = Interpreter::with_dialect(&exprs, dialect);
+    break_on_interrupt(&interpreter);
+
+    println!("Debugging {}. Type \"help\" for commands.", path);
+    let mut console = DebugConsole { source: source.lines().map(str::to_string).collect() };
+    let result = Debugger::new(&mut interpreter).run(&mut console);
+    if !report(result, Some((path, &source)), Format::Plain) {
+        std::process::exit(1);
+    }
+}
+
+/// The `debug` command's prompt, driven by pauses of the debugger.
+struct DebugConsole {
+    source: Vec<String>,
+}
+
+const DEBUG_HELP: &str = "\
+continue, c        run to the next breakpoint
+step, s            run to the next line, entering blocks
+next, n            run to the next line in this block
+out, o             run until this block is left
+break, b LINE      set a breakpoint
+delete, d LINE     remove a breakpoint
+breakpoints        list breakpoints
+stack, bt          show the open blocks
+vars               show all variables
+print, p EXPR      evaluate an expression
+watch, w EXPR      evaluate an expression at every pause
+unwatch N          remove a watch
+quit, q            stop debugging";
+
+impl DebugConsole {
+    fn show_line(&self, line: usize) {
+        let text = self.source.get(line.wrapping_sub(1)).map_or("", String::as_str);
+        println!("{:>5} | {}", line, text);
+    }
+
+    fn parse_line_number(arg: &str) -> Option<usize> {
+        let line = arg.trim().parse().ok();
+        if line.is_none() {
+            eprintln!("Expected a line number");
+        }
+        line
+    }
+}
+
+impl DebugHook for DebugConsole {
+    fn paused(&mut self, debugger: &mut Debugger, pause: Pause) -> Resume {
+        match pause {
+            Pause::Entry(_) => {}
+            Pause::Breakpoint(line) => println!("Breakpoint at line {}", line),
+            Pause::Step(_) => {}
+            Pause::Break(line) => println!("Break in line {}", line),
+        }
+        self.show_line(pause.line());
+
+        for (i, (source, value)) in debugger.watches().into_iter().enumerate() {
+            match value {
+                Ok(value) => println!("  watch {}: {} = {}", i, source, value),
+                Err(error) => println!("  watch {}: {} = <{}>", i, source, error),
+            }
+        }
+
+        loop {
+            print!("(debug) ");
+            io::stdout().flush().expect("error writing prompt");
+
+            let mut line = String::new();
+            if io::stdin().read_line(&mut line).expect("error reading input") == 0 {
+                std::process::exit(0);
+            }
+
+            let line = line.trim();
+            let (command, arg) = line.split_once(' ').unwrap_or((line, ""));
+
+            match command.to_lowercase().as_str() {
+                "" => {}
+                "continue" | "c" => return Resume::Continue,
+                "step" | "s" => return Resume::StepInto,
+                "next" | "n" => return Resume::StepOver,
+                "out" | "o" => return Resume::StepOut,
+                "break" | "b" => {
+                    if let Some(line) = Self::parse_line_number(arg) {
+                        debugger.set_breakpoint(line);
+                    }
+                }
+                "delete" | "d" => {
+                    if let Some(line) = Self::parse_line_number(arg) {
+                        if !debugger.clear_breakpoint(line) {
+                            eprintln!("No breakpoint at line {}", line);
+                        }
+                    }
+                }
+                "breakpoints" => {
+                    for line in debugger.breakpoints() {
+                        self.show_line(line);
+                    }
+                }
+                "stack" | "bt" => {
+                    for frame in debugger.call_stack() {
+                        println!("  {} at line {}", frame.name, frame.line);
+                    }
+                }
+                "vars" => {
+                    for (name, value) in debugger.variables() {
+                        println!("  {} = {}", name, value);
+                    }
+                }
+                "print" | "p" => match debugger.evaluate(arg) {
+                    Ok(Ok(value)) => println!("{}", value),
+                    Ok(Err(error)) => eprintln!("{}", error),
+                    Err(error) => eprintln!("{}", error),
+                },
+                "watch" | "w" => {
+                    if let Err(error) = debugger.add_watch(arg) {
+                        eprintln!("{}", error);
+                    }
+                }
+                "unwatch" => {
+                    if !arg.trim().parse().is_ok_and(|i| debugger.remove_watch(i)) {
+                        eprintln!("No such watch");
+                    }
+                }
+                "quit" | "q" => std::process::exit(0),
+                "help" | "h" => println!("{}", DEBUG_HELP),
+                _ => eprintln!("Unknown command {:?}; type \"help\" for commands", command),
+            }
+        }
+    }
+}
@@ -1,18 +1,28 @@
-use core::panic;
-
-use crate::lexer::Token;
+use crate::{
+    diagnostic,
+    dialect::Dialect,
+    error::SyntaxError,
+    lexer::{Lexer, Span, Token, KEYWORDS},
+};
 
 #[derive(Debug)]
 pub enum Expr {
     Bin(Box<Expr>, Token, Box<Expr>),
     Rel(Box<Expr>, Token, Box<Expr>),
     Num(i64),
+    /// A single-precision literal: one with an `E` exponent or a `!` suffix.
+    Single(f32),
+    Float(f64),
+    Neg(Box<Expr>),
     Identifier(String),
     Str(String),
     VarDec(String, Box<Expr>),
     Print(Box<Expr>),
-    Input(String, Box<Expr>),
-    If(Box<Expr>, Box<Expr>),
+    /// A prompt and the variable that receives the typed line.
+    Input(String, String),
+    /// A condition and the statements after `THEN`, up to the end of the
+    /// line.
+    If(Box<Expr>, Vec<Expr>),
     Assign(String, Box<Expr>),
     For {
         variable: String,
@@ -22,60 +32,316 @@ pub enum Expr {
         body: Vec<Expr>,
     },
     Call(String, Vec<Expr>),
+    Randomize(Option<Box<Expr>>),
+    Data(usize, Vec<Expr>),
+    Read(Vec<String>),
+    Restore(Option<usize>),
+    Select {
+        subject: Box<Expr>,
+        cases: Vec<Case>,
+        default: Option<Vec<Expr>>,
+    },
+    /// Marks the start of the statements on a source line.
+    Line(usize),
+    OnError(Option<usize>),
+    Resume(ResumeAt),
+    Raise(Box<Expr>),
+    End,
+    Stop,
+    Open {
+        path: Box<Expr>,
+        mode: FileMode,
+        number: Box<Expr>,
+        /// Record length of a `RANDOM` file.
+        len: Option<Box<Expr>>,
+    },
+    /// Closes the listed file numbers, or every open file when empty.
+    Close(Vec<Expr>),
+    PrintFile(Box<Expr>, Box<Expr>),
+    InputFile(Box<Expr>, Vec<String>),
+    LineInputFile(Box<Expr>, String),
+    /// Binds string variables to `(width, variable)` slices of a random
+    /// file's record buffer.
+    Field(Box<Expr>, Vec<(Expr, String)>),
+    /// Reads a record of a `RANDOM` file, or a variable's bytes from a
+    /// `BINARY` file. `position` is a record or byte number counted from 1.
+    Get {
+        number: Box<Expr>,
+        position: Option<Box<Expr>>,
+        variable: Option<String>,
+    },
+    Put {
+        number: Box<Expr>,
+        position: Option<Box<Expr>>,
+        variable: Option<String>,
+    },
+    LSet(String, Box<Expr>),
+    RSet(String, Box<Expr>),
+    /// Stands in for a statement that failed to parse. Running it raises a
+    /// syntax error.
+    Error(SyntaxError),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FileMode {
+    Input,
+    Output,
+    Append,
+    Random,
+    Binary,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResumeAt {
+    Same,
+    Next,
+    Line(usize),
+}
+
+#[derive(Debug)]
+pub struct Case {
+    pub tests: Vec<CaseTest>,
+    pub body: Vec<Expr>,
+}
+
+#[derive(Debug)]
+pub enum CaseTest {
+    Value(Expr),
+    Range(Expr, Expr),
+    Is(Token, Expr),
+}
+
+/// Lexes and parses a program, returning its statements along with every
+/// error found in its source, in source order.
+pub fn parse_program(source: &str, dialect: Dialect) -> (Vec<Expr>, Vec<SyntaxError>) {
+    let mut lexer = Lexer::with_dialect(source.to_string(), dialect);
+    let tokens = lexer.scan();
+
+    let mut parser = Parser::with_spans(&tokens, lexer.spans());
+    let exprs = parser.parse();
+
+    let mut errors: Vec<_> = lexer.errors().iter().chain(parser.errors()).cloned().collect();
+    errors.sort_by_key(|error| (error.span.line, error.span.col));
+    (exprs, errors)
 }
 
+/// Names a token, or the end of input, for an error message.
+fn describe(token: Option<&Token>) -> String {
+    match token {
+        Some(Token::Newline) => "end of line".to_string(),
+        Some(token) => format!("`{}`", token),
+        None => "end of input".to_string(),
+    }
+}
+
+/// The file number, position and variable of a `GET` or `PUT`.
+type RecordAccess = (Box<Expr>, Option<Box<Expr>>, Option<String>);
+
 pub struct Parser<'a> {
     current: usize,
-    tokens: &'a[Token]
+    tokens: &'a[Token],
+    /// Where each token came from, for locating errors. Without spans errors
+    /// only carry a line.
+    spans: &'a [Span],
+    /// Source line of the token at `current`, counted from 1.
+    line: usize,
+    errors: Vec<SyntaxError>,
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(tokens: &'a [Token]) -> Parser {
+    pub fn with_spans(tokens: &'a [Token], spans: &'a [Span]) -> Parser<'a> {
         Parser {
             current: 0,
-            tokens
+            tokens,
+            spans,
+            line: 1,
+            errors: Vec::new(),
         }
     }
 
+    /// An error at the token at `current`, or just past the last token at
+    /// the end of input.
+    fn error(&self, message: String) -> SyntaxError {
+        let span = match (self.spans.get(self.current), self.spans.last()) {
+            (Some(span), _) => *span,
+            (None, Some(last)) => Span { line: last.line, col: last.col + last.len, len: 0 },
+            (None, None) => Span { line: self.line, col: 0, len: 0 },
+        };
+        SyntaxError { message, span, help: None }
+    }
+
     fn advance(&mut self) {
+        if self.peek() == Some(&Token::Newline) {
+            self.line += 1;
+        }
         self.current += 1;
     }
 
-    fn expect(&mut self, expected: Token) {
-        let curr = &self.tokens[self.current];
-        
-        if curr == &expected {
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.current)
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), SyntaxError> {
+        let curr = self.peek();
+        if curr == Some(&expected) {
             self.advance();
-        } else {
-            panic!("Expected {:?}, got {:?}", expected, curr)
+            return Ok(());
+        }
+
+        let mut error = self.error(format!("Expected `{}`, got {}", expected, describe(curr)));
+        if let (Token::Keyword(keyword), Some(Token::Identifier(word) | Token::Keyword(word))) = (&expected, curr) {
+            if diagnostic::similar(word, &[keyword]).is_some() {
+                error.help = Some(format!("did you mean `{}`?", keyword));
+            }
         }
+        Err(error)
     }
 
-    fn parse_primary(&mut self) -> Expr {
-        let curr: &Token = &self.tokens[self.current];
+    fn at_separator(&self) -> bool {
+        matches!(self.peek(), None | Some(Token::Newline))
+            || matches!(self.peek(), Some(Token::Punc(punc)) if punc == ":")
+    }
+
+    fn skip_separators(&mut self) {
+        while self.current < self.tokens.len() && self.at_separator() {
+            self.advance();
+        }
+    }
+
+    /// Whether the upcoming tokens are the given space-separated keywords.
+    fn at_keywords(&self, words: &str) -> bool {
+        words.split(' ').enumerate().all(|(i, word)| {
+            matches!(self.tokens.get(self.current + i), Some(Token::Keyword(k)) if k == word)
+        })
+    }
+
+    /// Parses statements until one of `terminators` starts a statement,
+    /// leaving the terminator unconsumed. With no terminators the block runs
+    /// to the end of input. A statement with an error is recorded in `errors`
+    /// and replaced by an `Expr::Error`.
+    fn parse_block(&mut self, terminators: &[&str]) -> Result<Vec<Expr>, SyntaxError> {
+        let mut body = Vec::new();
+        let mut line = 0;
+        loop {
+            self.skip_separators();
+
+            if terminators.iter().any(|t| self.at_keywords(t)) {
+                break;
+            }
+            if self.current >= self.tokens.len() {
+                if terminators.is_empty() {
+                    break;
+                }
+                return Err(self.error(format!("Expected {}, got end of input", terminators.join(" or "))));
+            }
+
+            if self.line != line {
+                line = self.line;
+                body.push(Expr::Line(line));
+            }
+            let stmt = self.parse_stmt().and_then(|stmt| self.end_stmt().map(|_| stmt));
+            body.push(stmt.unwrap_or_else(|error| self.recover(error)));
+        }
+
+        Ok(body)
+    }
+
+    /// Records an error and skips to the end of the statement it occurred
+    /// in, so that parsing can carry on with the next statement.
+    fn recover(&mut self, error: SyntaxError) -> Expr {
+        self.errors.push(error.clone());
+        while !self.at_separator() {
+            self.advance();
+        }
+        Expr::Error(error)
+    }
+
+    /// Checks that a statement ends here, without consuming the separator.
+    fn check_end_stmt(&self) -> Result<(), SyntaxError> {
+        if !self.at_separator() {
+            let mut error = self.error(format!("Expected end of statement, got {}", describe(self.peek())));
+            if let Some(Token::Identifier(word)) = self.peek() {
+                error.help = diagnostic::similar(word, KEYWORDS).map(|keyword| format!("did you mean `{}`?", keyword));
+            }
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    fn end_stmt(&mut self) -> Result<(), SyntaxError> {
+        self.check_end_stmt()?;
+
+        if self.current < self.tokens.len() {
+            self.advance();
+        }
+        Ok(())
+    }
+
+    /// Converts a numeric literal. An `E` exponent or `!` suffix makes it
+    /// single precision, a `D` exponent or `#` suffix double precision, and
+    /// a `%` or `&` suffix an integer; the suffix wins over the exponent.
+    fn number(&self, text: &str) -> Result<Expr, SyntaxError> {
+        let (digits, suffix) = match text.chars().last() {
+            Some(c @ ('!' | '#' | '%' | '&')) => (&text[..text.len() - 1], Some(c)),
+            _ => (text, None),
+        };
+        let is_float = digits.contains(['.', 'E', 'D']);
+        let overflow = || self.error(format!("Overflow in literal {}", text));
+        let float = || match digits.replace('D', "E").parse::<f64>() {
+            Ok(f) if f.is_finite() => Ok(f),
+            Ok(_) => Err(overflow()),
+            Err(_) => Err(self.error(format!("Invalid number {}", text))),
+        };
+        let single = || Some(float()? as f32).filter(|f| f.is_finite()).ok_or_else(overflow);
+
+        Ok(match suffix {
+            Some('!') => Expr::Single(single()?),
+            Some('#') => Expr::Float(float()?),
+            Some(_) if is_float => Expr::Num(float()?.round() as i64),
+            _ if digits.contains('E') => Expr::Single(single()?),
+            _ if is_float => Expr::Float(float()?),
+            _ => match digits.parse() {
+                Ok(n) => Expr::Num(n),
+                Err(_) => Expr::Float(float()?),
+            },
+        })
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, SyntaxError> {
+        let Some(curr) = self.peek() else {
+            return Err(self.error("Expected an expression, got end of input".to_string()));
+        };
 
         match curr {
             Token::Number(num) => {
+                let number = self.number(num)?;
                 self.advance();
-                return Expr::Num(num.parse().unwrap());
+                Ok(number)
             },
+            Token::Identifier(_) if self.tokens.get(self.current + 1) == Some(&Token::Punc("(".to_string())) => {
+                self.parse_call()
+            }
             Token::Identifier(id) => {
                 self.advance();
-                return Expr::Identifier(id.clone());
+                Ok(Expr::Identifier(id.clone()))
             }
             Token::Str(str) => {
                 self.advance();
-                return Expr::Str(str.clone());
+                Ok(Expr::Str(str.clone()))
             }
             Token::BuiltIn(_) => {
-                return self.parse_call();
+                self.parse_call()
             } 
-            _ => panic!("Unexpected token in primary expression {:?}", curr)
+            Token::BinOp(op) if op == "-" => {
+                self.advance();
+                Ok(Expr::Neg(Box::new(self.parse_primary()?)))
+            }
+            _ => Err(self.error(format!("Expected an expression, got {}", describe(Some(curr)))))
         }
     }
 
-    fn parse_factor(&mut self) -> Expr {
-        let mut left = self.parse_primary();
+    fn parse_factor(&mut self) -> Result<Expr, SyntaxError> {
+        let mut left = self.parse_primary()?;
 
         while self.current < self.tokens.len() {
             let curr = &self.tokens[self.current];
@@ -86,16 +352,16 @@ impl<'a> Parser<'a> {
             };
 
             self.advance();
-            let right = self.parse_primary();
+            let right = self.parse_primary()?;
 
             left = Expr::Bin(Box::new(left), Token::BinOp(op), Box::new(right))
         }
 
-        return left;
+        Ok(left)
     }
 
-    fn parse_term(&mut self) -> Expr {
-        let mut left = self.parse_factor();
+    fn parse_term(&mut self) -> Result<Expr, SyntaxError> {
+        let mut left = self.parse_factor()?;
 
         while self.current < self.tokens.len() {
             let curr = &self.tokens[self.current];
@@ -106,16 +372,16 @@ impl<'a> Parser<'a> {
             };
 
             self.advance();
-            let right = self.parse_factor();
+            let right = self.parse_factor()?;
 
             left = Expr::Bin(Box::new(left), Token::BinOp(op), Box::new(right))
         }
 
-        return left;
+        Ok(left)
     }
     
-    fn parse_relational(&mut self) -> Expr {
-        let mut left = self.parse_term();
+    fn parse_relational(&mut self) -> Result<Expr, SyntaxError> {
+        let mut left = self.parse_term()?;
 
         while self.current < self.tokens.len() {
             let curr = &self.tokens[self.current];
@@ -128,165 +394,604 @@ impl<'a> Parser<'a> {
             };
 
             self.advance();
-            let right = self.parse_term();
+            let right = self.parse_term()?;
 
             left = Expr::Rel(Box::new(left), Token::RelOp(op), Box::new(right))
         }
 
-        return left;
+        Ok(left)
     }
 
-    fn parse_assign(&mut self) -> Expr {
-        let identifier = match &self.tokens[self.current] {
-            Token::Identifier(id) => id.clone(),
-            _ => panic!("Expected an identifier for variable declaration"),
-        };
-        self.advance();
+    /// Parses the variable being declared or assigned by a statement.
+    fn parse_variable(&mut self) -> Result<String, SyntaxError> {
+        match self.peek() {
+            Some(Token::Identifier(id)) => {
+                self.advance();
+                Ok(id.clone())
+            }
+            curr => Err(self.error(format!("Expected a variable, got {}", describe(curr)))),
+        }
+    }
+
+    fn parse_assign(&mut self) -> Result<Expr, SyntaxError> {
+        let identifier = self.parse_variable()?;
 
-        self.expect(Token::RelOp("=".to_string()));
+        self.expect(Token::RelOp("=".to_string()))?;
         
-        let expr = self.parse_expr();
+        let expr = self.parse_expr()?;
 
-        return Expr::Assign(identifier, Box::new(expr));
+        Ok(Expr::Assign(identifier, Box::new(expr)))
     }
 
-    fn parse_expr(&mut self) -> Expr {
-        return self.parse_relational();
+    fn parse_expr(&mut self) -> Result<Expr, SyntaxError> {
+        self.parse_relational()
     }
 
-    fn parse_for_loop(&mut self) -> Expr {
-        self.expect(Token::Keyword("FOR".to_string()));
+    /// Parses a `FOR` loop. When the `FOR` line itself is malformed, the body
+    /// is still parsed up to its `NEXT` so that the loop's end is not taken
+    /// for a stray statement.
+    fn parse_for_loop(&mut self) -> Result<Expr, SyntaxError> {
+        self.expect(Token::Keyword("FOR".to_string()))?;
 
-        let variable = match &self.tokens[self.current] {
-            Token::Identifier(id) => id.clone(),
-            _ => panic!("Expected an identifier for variable declaration"),
+        let header = self.parse_for_header().and_then(|header| self.end_stmt().map(|_| header));
+        let mut header = header.unwrap_or_else(|error| self.recover(error));
+        let variable = match &header {
+            Expr::For { variable, .. } => Some(variable.clone()),
+            _ => None,
         };
+
+        let body = self.parse_block(&["NEXT"])?;
         self.advance();
+        match (variable, self.peek()) {
+            (Some(variable), Some(Token::Identifier(_))) => self.expect(Token::Identifier(variable))?,
+            (None, Some(Token::Identifier(_))) => self.advance(),
+            _ => {}
+        }
+
+        if let Expr::For { body: header_body, .. } = &mut header {
+            *header_body = body;
+        }
+        Ok(header)
+    }
 
-        self.expect(Token::RelOp("=".to_string()));
+    /// Parses what follows `FOR`, returning a loop with an empty body.
+    fn parse_for_header(&mut self) -> Result<Expr, SyntaxError> {
+        let variable = self.parse_variable()?;
+
+        self.expect(Token::RelOp("=".to_string()))?;
         
-        let start = self.parse_expr();
+        let start = self.parse_expr()?;
 
-        self.expect(Token::Keyword("TO".to_string()));
-        let end = self.parse_expr();
+        self.expect(Token::Keyword("TO".to_string()))?;
+        let end = self.parse_expr()?;
 
         let step = if self.current < self.tokens.len() {
             if let Token::Keyword(keyword) = &self.tokens[self.current] {
                 if keyword == "STEP" {
                     self.advance();
-                    let step = self.parse_expr();
+                    let step = self.parse_expr()?;
                     Some(Box::new(step))
                 } else { None }
             } else { None }
         } else { None };
 
-        let mut body = Vec::new();
-        while self.current < self.tokens.len() {
-            match &self.tokens[self.current] {
-                Token::Keyword(word) if word == "NEXT" => {
-                    self.advance();
-                    self.expect(Token::Identifier(variable.clone()));
-                },
-                _ => { body.push(self.parse_stmt()); }
+        Ok(Expr::For {
+            variable, start: Box::new(start), end: Box::new(end),
+            step, body: Vec::new()
+        })
+    }
+
+    fn parse_select(&mut self) -> Result<Expr, SyntaxError> {
+        self.expect(Token::Keyword("SELECT".to_string()))?;
+        self.expect(Token::Keyword("CASE".to_string()))?;
+
+        let subject = self.parse_expr().and_then(|subject| self.end_stmt().map(|_| subject));
+        let subject = subject.unwrap_or_else(|error| self.recover(error));
+
+        let mut cases = Vec::new();
+        let mut default = None;
+        loop {
+            self.skip_separators();
+            if self.at_keywords("END SELECT") {
+                self.advance();
+                self.advance();
+                break;
+            }
+
+            if let Err(error) = self.expect(Token::Keyword("CASE".to_string())) {
+                if self.peek().is_none() {
+                    return Err(error);
+                }
+                self.recover(error);
+                continue;
+            }
+            if default.is_some() {
+                let error = self.error("CASE ELSE must be the last case in SELECT CASE".to_string());
+                self.errors.push(error);
             }
+
+            if self.at_keywords("ELSE") {
+                self.advance();
+                if let Err(error) = self.end_stmt() {
+                    self.recover(error);
+                }
+                default = Some(self.parse_block(&["CASE", "END SELECT"])?);
+                continue;
+            }
+
+            let tests = self.parse_case_tests().unwrap_or_else(|error| {
+                self.recover(error);
+                Vec::new()
+            });
+
+            let body = self.parse_block(&["CASE", "END SELECT"])?;
+            cases.push(Case { tests, body });
         }
 
-        Expr::For {
-            variable, start: Box::new(start), end: Box::new(end),
-            step, body
+        if let Expr::Error(error) = subject {
+            return Ok(Expr::Error(error));
+        }
+        Ok(Expr::Select { subject: Box::new(subject), cases, default })
+    }
+
+    /// Parses the comma-separated tests of a `CASE` line.
+    fn parse_case_tests(&mut self) -> Result<Vec<CaseTest>, SyntaxError> {
+        let mut tests = Vec::new();
+        loop {
+            tests.push(self.parse_case_test()?);
+
+            if self.peek() != Some(&Token::Punc(",".to_string())) {
+                break;
+            }
+            self.advance();
+        }
+        self.end_stmt()?;
+
+        Ok(tests)
+    }
+
+    fn parse_case_test(&mut self) -> Result<CaseTest, SyntaxError> {
+        if self.at_keywords("IS") {
+            self.advance();
+
+            let op = match self.peek() {
+                Some(Token::RelOp(op)) => Token::RelOp(op.clone()),
+                curr => return Err(self.error(format!("Expected a relational operator after IS, got {}", describe(curr)))),
+            };
+            self.advance();
+
+            return Ok(CaseTest::Is(op, self.parse_term()?));
+        }
+
+        let value = self.parse_expr()?;
+        if self.at_keywords("TO") {
+            self.advance();
+            return Ok(CaseTest::Range(value, self.parse_expr()?));
         }
+
+        Ok(CaseTest::Value(value))
     }
 
-    fn parse_if_stmt(&mut self) -> Expr {
-        self.expect(Token::Keyword("IF".to_string()));
+    fn parse_if_stmt(&mut self) -> Result<Expr, SyntaxError> {
+        self.expect(Token::Keyword("IF".to_string()))?;
 
-        let expr = self.parse_expr();
-        self.expect(Token::Keyword("THEN".to_string()));
+        let expr = self.parse_expr()?;
+        self.expect(Token::Keyword("THEN".to_string()))?;
+
+        Ok(Expr::If(Box::new(expr), self.parse_consequent()))
+    }
 
-        let consequent = self.parse_stmt();
+    /// Parses every `:`-separated statement after `THEN` on the same line,
+    /// all of which depend on the condition.
+    fn parse_consequent(&mut self) -> Vec<Expr> {
+        let mut consequent = Vec::new();
+        loop {
+            let stmt = self.parse_stmt().and_then(|stmt| self.check_end_stmt().map(|_| stmt));
+            consequent.push(stmt.unwrap_or_else(|error| self.recover(error)));
 
-        return Expr::If(Box::new(expr), Box::new(consequent));
+            if !matches!(self.peek(), Some(Token::Punc(punc)) if punc == ":") {
+                break;
+            }
+            self.advance();
+            if matches!(self.peek(), None | Some(Token::Newline)) {
+                break;
+            }
+        }
+        consequent
     }
 
-    fn parse_var_dec(&mut self) -> Expr {
-        self.expect(Token::Keyword("LET".to_string()));
+    fn parse_var_dec(&mut self) -> Result<Expr, SyntaxError> {
+        self.expect(Token::Keyword("LET".to_string()))?;
     
-        let identifier = match &self.tokens[self.current] {
-            Token::Identifier(id) => id.clone(),
-            _ => panic!("Expected an identifier for variable declaration"),
-        };
-        self.advance();
+        let identifier = self.parse_variable()?;
     
-        self.expect(Token::RelOp("=".to_string()));
-        let expr = self.parse_expr();
+        self.expect(Token::RelOp("=".to_string()))?;
+        let expr = self.parse_expr()?;
         
-        Expr::VarDec(identifier, Box::new(expr))
+        Ok(Expr::VarDec(identifier, Box::new(expr)))
     }
 
-    fn parse_print(&mut self) -> Expr {
-        self.expect(Token::Keyword("PRINT".to_string()));
+    fn parse_print(&mut self) -> Result<Expr, SyntaxError> {
+        self.expect(Token::Keyword("PRINT".to_string()))?;
+
+        if self.peek() == Some(&Token::Punc("#".to_string())) {
+            let number = self.parse_file_number()?;
+            self.expect(Token::Punc(",".to_string()))?;
+            return Ok(Expr::PrintFile(Box::new(number), Box::new(self.parse_expr()?)));
+        }
     
-        let expr = self.parse_expr();
-        Expr::Print(Box::new(expr))
+        let expr = self.parse_expr()?;
+        Ok(Expr::Print(Box::new(expr)))
     }
     
-    fn parse_input(&mut self) -> Expr  {
-        self.expect(Token::Keyword("INPUT".to_string()));
+    fn parse_input(&mut self) -> Result<Expr, SyntaxError>  {
+        self.expect(Token::Keyword("INPUT".to_string()))?;
+
+        if self.peek() == Some(&Token::Punc("#".to_string())) {
+            let number = self.parse_file_number()?;
+            self.expect(Token::Punc(",".to_string()))?;
+            return Ok(Expr::InputFile(Box::new(number), self.parse_identifiers("INPUT")?));
+        }
+
+        if let Some(Token::Str(s)) = self.peek() {
+            self.advance();
+            return Ok(Expr::Input(s.clone(), self.parse_variable()?));
+        }
+
+        Err(self.error("Unexpected expression for input".to_string()))
+    }
+
+    fn parse_data(&mut self) -> Result<Expr, SyntaxError> {
+        self.expect(Token::Keyword("DATA".to_string()))?;
+        let line = self.line;
 
-        if let Token::Str(s) = &self.tokens[self.current] {
+        let mut values = Vec::new();
+        loop {
+            let value = match self.peek() {
+                Some(Token::Number(num)) => self.number(num)?,
+                Some(Token::BinOp(op)) if op == "-" => {
+                    self.advance();
+                    match self.peek() {
+                        Some(Token::Number(num)) => Expr::Neg(Box::new(self.number(num)?)),
+                        _ => return Err(self.error("Expected a number after '-' in DATA statement".to_string())),
+                    }
+                }
+                Some(Token::Str(str)) => Expr::Str(str.clone()),
+                Some(Token::Identifier(id)) => Expr::Str(id.clone()),
+                curr => return Err(self.error(format!("Expected a constant in DATA statement, got {}", describe(curr)))),
+            };
             self.advance();
-            let identifier = self.parse_primary();
+            values.push(value);
 
-            return Expr::Input(s.clone(), Box::new(identifier));
+            if self.peek() != Some(&Token::Punc(",".to_string())) {
+                break;
+            }
+            self.advance();
         }
 
-        panic!("Unexpected expression for input");
+        Ok(Expr::Data(line, values))
     }
 
-    fn parse_call(&mut self) -> Expr {
-        let func = match &self.tokens[self.current] {
-            Token::BuiltIn(id) => id.clone(),
-            _ => panic!("Expected function name, got {:?}", &self.tokens[self.current]),
+    fn parse_read(&mut self) -> Result<Expr, SyntaxError> {
+        self.expect(Token::Keyword("READ".to_string()))?;
+        Ok(Expr::Read(self.parse_identifiers("READ")?))
+    }
+
+    /// Parses a comma-separated list of variables for `statement`.
+    fn parse_identifiers(&mut self, statement: &str) -> Result<Vec<String>, SyntaxError> {
+        let mut variables = Vec::new();
+        loop {
+            match self.peek() {
+                Some(Token::Identifier(id)) => variables.push(id.clone()),
+                _ => return Err(self.error(format!("Expected an identifier in {} statement", statement))),
+            }
+            self.advance();
+
+            if self.peek() != Some(&Token::Punc(",".to_string())) {
+                break;
+            }
+            self.advance();
+        }
+
+        Ok(variables)
+    }
+
+    /// Parses a file number, where the leading `#` is optional.
+    fn parse_file_number(&mut self) -> Result<Expr, SyntaxError> {
+        if self.peek() == Some(&Token::Punc("#".to_string())) {
+            self.advance();
+        }
+        self.parse_expr()
+    }
+
+    fn parse_open(&mut self) -> Result<Expr, SyntaxError> {
+        self.expect(Token::Keyword("OPEN".to_string()))?;
+
+        let path = self.parse_expr()?;
+        self.expect(Token::Keyword("FOR".to_string()))?;
+
+        let mode = match self.peek() {
+            Some(Token::Keyword(k)) if k == "INPUT" => FileMode::Input,
+            Some(Token::Keyword(k)) if k == "OUTPUT" => FileMode::Output,
+            Some(Token::Keyword(k)) if k == "APPEND" => FileMode::Append,
+            Some(Token::Keyword(k)) if k == "RANDOM" => FileMode::Random,
+            Some(Token::Keyword(k)) if k == "BINARY" => FileMode::Binary,
+            curr => return Err(self.error(format!("Expected a file mode after FOR, got {}", describe(curr)))),
         };
         self.advance();
-    
-        self.expect(Token::Punc("(".to_string()));
-        
+
+        self.expect(Token::Keyword("AS".to_string()))?;
+        let number = self.parse_file_number()?;
+
+        let mut len = None;
+        if self.peek() == Some(&Token::BuiltIn("LEN".to_string())) {
+            if mode != FileMode::Random {
+                return Err(self.error("LEN is only allowed when opening a RANDOM file".to_string()));
+            }
+            self.advance();
+            self.expect(Token::RelOp("=".to_string()))?;
+            len = Some(Box::new(self.parse_expr()?));
+        }
+
+        Ok(Expr::Open { path: Box::new(path), mode, number: Box::new(number), len })
+    }
+
+    fn parse_field(&mut self) -> Result<Expr, SyntaxError> {
+        self.expect(Token::Keyword("FIELD".to_string()))?;
+
+        let number = self.parse_file_number()?;
+        let mut fields = Vec::new();
+        while self.peek() == Some(&Token::Punc(",".to_string())) {
+            self.advance();
+
+            let width = self.parse_expr()?;
+            self.expect(Token::Keyword("AS".to_string()))?;
+            let variable = match self.peek() {
+                Some(Token::Identifier(id)) => id.clone(),
+                curr => return Err(self.error(format!("Expected an identifier in FIELD statement, got {}", describe(curr)))),
+            };
+            self.advance();
+
+            fields.push((width, variable));
+        }
+
+        Ok(Expr::Field(Box::new(number), fields))
+    }
+
+    /// Parses the operands shared by `GET` and `PUT`:
+    /// `#n [, [position] [, variable]]`.
+    fn parse_record_access(&mut self) -> Result<RecordAccess, SyntaxError> {
+        self.advance();
+
+        let number = self.parse_file_number()?;
+        let comma = Token::Punc(",".to_string());
+        let mut position = None;
+        let mut variable = None;
+
+        if self.peek() == Some(&comma) {
+            self.advance();
+            if self.peek() != Some(&comma) && !self.at_separator() {
+                position = Some(Box::new(self.parse_expr()?));
+            }
+
+            if self.peek() == Some(&comma) {
+                self.advance();
+                match self.peek() {
+                    Some(Token::Identifier(id)) => variable = Some(id.clone()),
+                    curr => return Err(self.error(format!("Expected an identifier after file position, got {}", describe(curr)))),
+                }
+                self.advance();
+            }
+        }
+
+        Ok((Box::new(number), position, variable))
+    }
+
+    fn parse_justify(&mut self) -> Result<Expr, SyntaxError> {
+        let right = self.at_keywords("RSET");
+        self.advance();
+
+        let variable = match self.peek() {
+            Some(Token::Identifier(id)) => id.clone(),
+            curr => return Err(self.error(format!("Expected an identifier after LSET or RSET, got {}", describe(curr)))),
+        };
+        self.advance();
+
+        self.expect(Token::RelOp("=".to_string()))?;
+        let expr = Box::new(self.parse_expr()?);
+
+        Ok(if right { Expr::RSet(variable, expr) } else { Expr::LSet(variable, expr) })
+    }
+
+    fn parse_close(&mut self) -> Result<Expr, SyntaxError> {
+        self.expect(Token::Keyword("CLOSE".to_string()))?;
+
+        let mut numbers = Vec::new();
+        while !self.at_separator() {
+            numbers.push(self.parse_file_number()?);
+
+            if self.peek() != Some(&Token::Punc(",".to_string())) {
+                break;
+            }
+            self.advance();
+        }
+
+        Ok(Expr::Close(numbers))
+    }
+
+    fn parse_line_input(&mut self) -> Result<Expr, SyntaxError> {
+        self.expect(Token::Keyword("LINE".to_string()))?;
+        self.expect(Token::Keyword("INPUT".to_string()))?;
+
+        if self.peek() != Some(&Token::Punc("#".to_string())) {
+            return Err(self.error("Expected a file number after LINE INPUT".to_string()));
+        }
+        let number = self.parse_file_number()?;
+        self.expect(Token::Punc(",".to_string()))?;
+
+        let variable = match self.peek() {
+            Some(Token::Identifier(id)) => id.clone(),
+            curr => return Err(self.error(format!("Expected an identifier in LINE INPUT statement, got {}", describe(curr)))),
+        };
+        self.advance();
+
+        Ok(Expr::LineInputFile(Box::new(number), variable))
+    }
+
+    fn parse_restore(&mut self) -> Result<Expr, SyntaxError> {
+        self.expect(Token::Keyword("RESTORE".to_string()))?;
+
+        if self.at_separator() {
+            return Ok(Expr::Restore(None));
+        }
+
+        Ok(Expr::Restore(Some(self.parse_line_number()?)))
+    }
+
+    fn parse_line_number(&mut self) -> Result<usize, SyntaxError> {
+        match self.peek() {
+            Some(Token::Number(num)) => {
+                let line = num.parse().map_err(|_| self.error(format!("Invalid line number {}", num)))?;
+                self.advance();
+                Ok(line)
+            }
+            curr => Err(self.error(format!("Expected a line number, got {}", describe(curr)))),
+        }
+    }
+
+    fn parse_on_error(&mut self) -> Result<Expr, SyntaxError> {
+        self.expect(Token::Keyword("ON".to_string()))?;
+        self.expect(Token::Keyword("ERROR".to_string()))?;
+        self.expect(Token::Keyword("GOTO".to_string()))?;
+
+        Ok(match self.parse_line_number()? {
+            0 => Expr::OnError(None),
+            line => Expr::OnError(Some(line)),
+        })
+    }
+
+    fn parse_resume(&mut self) -> Result<Expr, SyntaxError> {
+        self.expect(Token::Keyword("RESUME".to_string()))?;
+
+        if self.at_separator() {
+            return Ok(Expr::Resume(ResumeAt::Same));
+        }
+        if self.at_keywords("NEXT") {
+            self.advance();
+            return Ok(Expr::Resume(ResumeAt::Next));
+        }
+
+        Ok(match self.parse_line_number()? {
+            0 => Expr::Resume(ResumeAt::Same),
+            line => Expr::Resume(ResumeAt::Line(line)),
+        })
+    }
+
+    fn parse_raise(&mut self) -> Result<Expr, SyntaxError> {
+        self.expect(Token::Keyword("ERROR".to_string()))?;
+        Ok(Expr::Raise(Box::new(self.parse_expr()?)))
+    }
+
+    fn parse_randomize(&mut self) -> Result<Expr, SyntaxError> {
+        self.expect(Token::Keyword("RANDOMIZE".to_string()))?;
+
+        if self.at_separator() {
+            return Ok(Expr::Randomize(None));
+        }
+
+        Ok(Expr::Randomize(Some(Box::new(self.parse_expr()?))))
+    }
+
+    fn parse_call(&mut self) -> Result<Expr, SyntaxError> {
+        let func = match self.peek() {
+            Some(Token::BuiltIn(id)) => id.clone(),
+            Some(Token::Identifier(id)) => id.to_uppercase(),
+            curr => return Err(self.error(format!("Expected function name, got {}", describe(curr)))),
+        };
+        self.advance();
+
         let mut args = Vec::new();
-        while self.current < self.tokens.len() {
-            let arg = self.parse_expr();
+        if self.peek() != Some(&Token::Punc("(".to_string())) {
+            return Ok(Expr::Call(func, args));
+        }
+        self.advance();
+
+        if self.peek() == Some(&Token::Punc(")".to_string())) {
+            self.advance();
+            return Ok(Expr::Call(func, args));
+        }
+
+        loop {
+            let arg = self.parse_expr()?;
             args.push(arg);
             
-            if matches!(self.tokens[self.current], Token::Punc(ref punc) if punc == &",".to_string()) {
+            if self.peek() == Some(&Token::Punc(",".to_string())) {
                 self.advance();
-            } else if matches!(self.tokens[self.current], Token::Punc(ref punc) if punc == &")".to_string()) {
+            } else if self.peek() == Some(&Token::Punc(")".to_string())) {
                 self.advance();
                 break;
             } else {
-                panic!("Expected comma or closing parenthesis in function call");
+                return Err(self.error("Expected comma or closing parenthesis in function call".to_string()));
             }
         }
         
-        Expr::Call(func, args)
+        Ok(Expr::Call(func, args))
     }
     
 
-    fn parse_stmt(&mut self) -> Expr {
-        match &self.tokens[self.current] {
+    fn parse_stmt(&mut self) -> Result<Expr, SyntaxError> {
+        let Some(curr) = self.peek() else {
+            return Err(self.error("Expected a statement, got end of input".to_string()));
+        };
+
+        match curr {
             Token::Keyword(word) => match word.as_str() {
                 "LET" => self.parse_var_dec(),
                 "PRINT" => self.parse_print(),
                 "INPUT" => self.parse_input(),
                 "IF" => self.parse_if_stmt(),
                 "FOR" => self.parse_for_loop(),
-                _ => panic!("Unknown keyword"),
+                "RANDOMIZE" => self.parse_randomize(),
+                "DATA" => self.parse_data(),
+                "READ" => self.parse_read(),
+                "RESTORE" => self.parse_restore(),
+                "SELECT" => self.parse_select(),
+                "ON" => self.parse_on_error(),
+                "RESUME" => self.parse_resume(),
+                "ERROR" => self.parse_raise(),
+                "OPEN" => self.parse_open(),
+                "CLOSE" => self.parse_close(),
+                "LINE" => self.parse_line_input(),
+                "FIELD" => self.parse_field(),
+                "GET" => {
+                    let (number, position, variable) = self.parse_record_access()?;
+                    Ok(Expr::Get { number, position, variable })
+                }
+                "PUT" => {
+                    let (number, position, variable) = self.parse_record_access()?;
+                    Ok(Expr::Put { number, position, variable })
+                }
+                "LSET" | "RSET" => self.parse_justify(),
+                "END" => {
+                    self.advance();
+                    Ok(Expr::End)
+                }
+                "STOP" => {
+                    self.advance();
+                    Ok(Expr::Stop)
+                }
+                "NEXT" => Err(self.error("NEXT without FOR".to_string())),
+                _ => Err(self.error(format!("`{}` cannot start a statement", word))),
             },
             Token::BuiltIn(_) => self.parse_call(),
-            Token::Identifier(_) => {
-                if self.tokens.len() > self.current + 1 {
-                    if let Token::RelOp(op) = &self.tokens[self.current + 1] {
-                        if op == "=" { return self.parse_assign(); }
-                    }
+            Token::Identifier(name) => {
+                if let Some(Token::RelOp(op)) = self.tokens.get(self.current + 1) {
+                    if op == "=" { return self.parse_assign(); }
+                }
+                if let Some(keyword) = self.misspelled_keyword(name) {
+                    let mut error = self.error(format!("Unknown statement `{}`", name));
+                    error.help = Some(format!("did you mean `{}`?", keyword));
+                    return Err(error);
                 }
                 self.parse_expr()
             },
@@ -294,13 +999,35 @@ impl<'a> Parser<'a> {
         }
     }
 
-    pub fn parse(&mut self) -> Vec<Expr> {
-        let mut exprs = Vec::new();
+    /// A keyword that an identifier starting a statement was probably meant
+    /// to be. Only identifiers followed by something that cannot continue an
+    /// expression count, since those statements would fail anyway.
+    fn misspelled_keyword(&self, name: &str) -> Option<&'static str> {
+        match self.tokens.get(self.current + 1) {
+            None | Some(Token::Newline | Token::BinOp(_) | Token::RelOp(_)) => None,
+            Some(Token::Punc(punc)) if punc == "(" || punc == ":" => None,
+            _ => diagnostic::similar(name, KEYWORDS),
+        }
+    }
 
-        while self.current < self.tokens.len() {
-            exprs.push(self.parse_stmt());
+    /// Parses a single expression, such as a debugger watch, that must make
+    /// up all of the tokens.
+    pub fn parse_expression(&mut self) -> Result<Expr, SyntaxError> {
+        let expr = self.parse_expr()?;
+        match self.peek() {
+            Some(curr) => Err(self.error(format!("Expected end of expression, got {}", describe(Some(curr))))),
+            None => Ok(expr),
         }
+    }
 
-        return exprs;
+    /// Parses the whole program in one pass. Statements with syntax errors
+    /// become `Expr::Error` nodes and the errors are kept in `errors`, so a
+    /// caller should check them before running the program.
+    pub fn parse(&mut self) -> Vec<Expr> {
+        self.parse_block(&[]).expect("errors in the top-level block are recorded")
+    }
+
+    pub fn errors(&self) -> &[SyntaxError] {
+        &self.errors
     }
-}
\ No newline at end of file
+}
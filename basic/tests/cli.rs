@@ -0,0 +1,49 @@
+use std::{
+    path::PathBuf,
+    process::{Command, Output},
+};
+
+/// Writes a program to a file of its own for a test to pass to the CLI.
+fn program(name: &str, source: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("basic-cli-{}-{}.bas", std::process::id(), name));
+    std::fs::write(&path, source).expect("error writing test program");
+    path
+}
+
+fn basic(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_basic")).args(args).output().expect("error running basic")
+}
+
+#[test]
+fn check_reports_every_file_and_fails_if_any_is_broken() {
+    let good = program("good", "LET a = 1\nPRINT a\n");
+    let bad = program("bad", "IF a THN PRINT 1\nLET = 2\n");
+    let (good, bad) = (good.to_str().unwrap(), bad.to_str().unwrap());
+
+    let output = basic(&["check", good]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stderr.is_empty());
+
+    let output = basic(&["--format=json", "check", bad, good]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let lines: Vec<serde_json::Value> = stderr.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines.iter().all(|line| line["file"] == bad));
+    assert_eq!((lines[0]["line"].clone(), lines[1]["line"].clone()), (1.into(), 2.into()));
+
+    std::fs::remove_file(good).ok();
+    std::fs::remove_file(bad).ok();
+}
+
+#[test]
+fn check_without_files_is_a_usage_error() {
+    assert_eq!(basic(&["check"]).status.code(), Some(2));
+}
+
+#[test]
+fn check_fails_on_missing_files() {
+    let output = basic(&["check", "/nonexistent/missing.bas"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().starts_with("/nonexistent/missing.bas: "));
+}
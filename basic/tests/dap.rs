@@ -0,0 +1,211 @@
+use std::{io::Cursor, path::PathBuf};
+
+use basic::{dap, dialect::Dialect};
+use serde_json::{json, Value as Json};
+
+const PROGRAM: &str = "\
+LET a = 1
+FOR i = 1 TO 2
+LET a = a * 10
+NEXT
+INPUT \"n?\" n
+PRINT a + n
+";
+
+/// Writes `PROGRAM` to a file of its own for a test to launch.
+fn program(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("basic-dap-{}-{}.bas", std::process::id(), name));
+    std::fs::write(&path, PROGRAM).expect("error writing test program");
+    path
+}
+
+/// Serves a whole session of `requests`, numbered in order, and returns
+/// everything the adapter sent back.
+fn session(requests: &[Json]) -> Vec<Json> {
+    let mut input = Vec::new();
+    for (seq, request) in requests.iter().enumerate() {
+        let mut request = request.clone();
+        request["seq"] = json!(seq + 1);
+        request["type"] = json!("request");
+
+        let body = request.to_string();
+        input.extend(format!("Content-Length: {}\r\n\r\n{}", body.len(), body).into_bytes());
+    }
+
+    let mut output = Vec::new();
+    dap::serve(Cursor::new(input), &mut output, Dialect::default()).expect("error serving session");
+    messages(&output)
+}
+
+fn messages(mut output: &[u8]) -> Vec<Json> {
+    let mut messages = Vec::new();
+    while !output.is_empty() {
+        let text = std::str::from_utf8(output).expect("output is not UTF-8");
+        let (header, rest) = text.split_once("\r\n\r\n").expect("message without a header");
+        let length: usize = header.trim_start_matches("Content-Length: ").parse().expect("bad Content-Length");
+
+        messages.push(serde_json::from_str(&rest[..length]).expect("message is not JSON"));
+        output = &rest.as_bytes()[length..];
+    }
+    messages
+}
+
+fn request(command: &str, arguments: Json) -> Json {
+    json!({ "command": command, "arguments": arguments })
+}
+
+fn launch(path: &PathBuf, stop_on_entry: bool) -> Json {
+    request("launch", json!({ "program": path, "stopOnEntry": stop_on_entry }))
+}
+
+fn response<'m>(messages: &'m [Json], command: &str) -> &'m Json {
+    messages.iter().find(|m| m["type"] == "response" && m["command"] == command)
+        .unwrap_or_else(|| panic!("no response to {}", command))
+}
+
+fn responses<'m>(messages: &'m [Json], command: &str) -> Vec<&'m Json> {
+    messages.iter().filter(|m| m["type"] == "response" && m["command"] == command).collect()
+}
+
+fn events<'m>(messages: &'m [Json], event: &str) -> Vec<&'m Json> {
+    messages.iter().filter(|m| m["type"] == "event" && m["event"] == event).collect()
+}
+
+fn output(messages: &[Json], category: &str) -> String {
+    events(messages, "output").iter()
+        .filter(|e| e["body"]["category"] == category)
+        .map(|e| e["body"]["output"].as_str().unwrap_or_default())
+        .collect()
+}
+
+#[test]
+fn breakpoints_steps_variables_and_evaluation() {
+    let path = program("steps");
+    let messages = session(&[
+        request("initialize", json!({ "adapterID": "basic" })),
+        request("setBreakpoints", json!({ "breakpoints": [{ "line": 3 }] })),
+        launch(&path, false),
+        request("configurationDone", json!({})),
+        request("threads", json!({})),
+        request("stackTrace", json!({ "threadId": 1 })),
+        request("scopes", json!({ "frameId": 0 })),
+        request("variables", json!({ "variablesReference": 1 })),
+        request("evaluate", json!({ "expression": "a * 2", "context": "watch" })),
+        request("evaluate", json!({ "expression": "a +", "context": "watch" })),
+        request("evaluate", json!({ "expression": "1.1! * 2", "context": "watch" })),
+        request("setBreakpoints", json!({ "breakpoints": [] })),
+        request("next", json!({ "threadId": 1 })),
+        request("variables", json!({ "variablesReference": 1 })),
+        request("continue", json!({ "threadId": 1 })),
+        request("evaluate", json!({ "expression": "5", "context": "repl" })),
+    ]);
+    std::fs::remove_file(&path).ok();
+
+    assert_eq!(response(&messages, "initialize")["body"]["supportsConfigurationDoneRequest"], true);
+    assert_eq!(events(&messages, "initialized").len(), 1);
+    let breakpoints = responses(&messages, "setBreakpoints");
+    assert_eq!(breakpoints[0]["body"]["breakpoints"], json!([{ "verified": true, "line": 3 }]));
+    assert_eq!(breakpoints[1]["body"]["breakpoints"], json!([]));
+
+    let stopped: Vec<_> = events(&messages, "stopped").iter().map(|e| e["body"]["reason"].clone()).collect();
+    assert_eq!(stopped, [json!("breakpoint"), json!("step")]);
+
+    assert_eq!(response(&messages, "threads")["body"]["threads"], json!([{ "id": 1, "name": "main" }]));
+    let frames = &response(&messages, "stackTrace")["body"]["stackFrames"];
+    assert_eq!(frames[0]["name"], "FOR i");
+    assert_eq!(frames[0]["line"], 3);
+    assert_eq!(frames[1]["name"], "main");
+    assert_eq!(frames[1]["line"], 2);
+    assert_eq!(response(&messages, "scopes")["body"]["scopes"][0]["variablesReference"], 1);
+
+    let variables = responses(&messages, "variables");
+    assert_eq!(variables[0]["body"]["variables"], json!([
+        { "name": "a", "value": "1", "type": "Integer", "variablesReference": 0 },
+        { "name": "i", "value": "1", "type": "Integer", "variablesReference": 0 },
+    ]));
+    assert_eq!(variables[1]["body"]["variables"], json!([
+        { "name": "a", "value": "10", "type": "Integer", "variablesReference": 0 },
+        { "name": "i", "value": "2", "type": "Integer", "variablesReference": 0 },
+    ]));
+
+    let evaluations = responses(&messages, "evaluate");
+    assert_eq!(evaluations[0]["body"]["result"], "2");
+    assert_eq!(evaluations[1]["success"], false);
+    assert_eq!(evaluations[2]["body"]["result"], "2.2");
+    assert_eq!(evaluations[2]["body"]["type"], "Single");
+    assert_eq!(evaluations[3]["success"], true);
+
+    assert_eq!(output(&messages, "console"), "n?\n");
+    assert_eq!(output(&messages, "stdout"), "105\n");
+    assert_eq!(events(&messages, "exited")[0]["body"]["exitCode"], 0);
+    assert_eq!(events(&messages, "terminated").len(), 1);
+}
+
+#[test]
+fn configuration_done_before_launch_starts_the_program() {
+    let path = program("early-configuration");
+    let messages = session(&[
+        request("initialize", json!({})),
+        request("configurationDone", json!({})),
+        launch(&path, true),
+        request("stepIn", json!({ "threadId": 1 })),
+    ]);
+    std::fs::remove_file(&path).ok();
+
+    assert_eq!(response(&messages, "launch")["success"], true);
+    let stopped: Vec<_> = events(&messages, "stopped").iter().map(|e| e["body"]["reason"].clone()).collect();
+    assert_eq!(stopped, [json!("entry"), json!("step")]);
+}
+
+#[test]
+fn a_program_with_syntax_errors_fails_to_launch() {
+    let path = std::env::temp_dir().join(format!("basic-dap-{}-broken.bas", std::process::id()));
+    std::fs::write(&path, "IF a THN PRINT 1\n").expect("error writing test program");
+
+    let messages = session(&[
+        request("initialize", json!({})),
+        launch(&path, false),
+        request("disconnect", json!({})),
+    ]);
+    std::fs::remove_file(&path).ok();
+
+    let launch = response(&messages, "launch");
+    assert_eq!(launch["success"], false);
+    assert!(launch["message"].as_str().unwrap().contains("Expected `THEN`"), "{}", launch);
+    assert_eq!(response(&messages, "disconnect")["success"], true);
+}
+
+#[test]
+fn runtime_errors_end_the_session() {
+    let path = std::env::temp_dir().join(format!("basic-dap-{}-error.bas", std::process::id()));
+    std::fs::write(&path, "LET a = 1 / 0\n").expect("error writing test program");
+
+    let messages = session(&[
+        request("initialize", json!({})),
+        launch(&path, false),
+        request("configurationDone", json!({})),
+    ]);
+    std::fs::remove_file(&path).ok();
+
+    assert_eq!(output(&messages, "stderr"), "Division by zero in line 1\n");
+    assert_eq!(events(&messages, "exited")[0]["body"]["exitCode"], 1);
+}
+
+#[test]
+fn disconnect_stops_a_running_program() {
+    let path = std::env::temp_dir().join(format!("basic-dap-{}-endless.bas", std::process::id()));
+    std::fs::write(&path, "LET a = 0\nFOR i = 1 TO 2000000000\nLET a = a + 1\nNEXT\n").expect("error writing test program");
+
+    for command in ["disconnect", "terminate"] {
+        let messages = session(&[
+            request("initialize", json!({})),
+            launch(&path, false),
+            request("configurationDone", json!({})),
+            request(command, json!({})),
+        ]);
+
+        assert_eq!(response(&messages, command)["success"], true);
+        assert!(events(&messages, "exited").is_empty());
+    }
+    std::fs::remove_file(&path).ok();
+}
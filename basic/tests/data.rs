@@ -0,0 +1,47 @@
+use basic::{
+    dialect::Dialect,
+    error::{ErrorKind, RuntimeError},
+    interpreter::{Completion, Interpreter, Value},
+    parser::{self, Expr},
+};
+
+fn parse(source: &str) -> Vec<Expr> {
+    let (exprs, errors) = parser::parse_program(source, Dialect::default());
+    assert!(errors.is_empty(), "syntax errors: {:?}", errors);
+    exprs
+}
+
+fn integer(interpreter: &Interpreter, name: &str) -> i64 {
+    match interpreter.get_var(name) {
+        Some(Value::Integer(n)) => *n,
+        val => panic!("{} is {:?}", name, val),
+    }
+}
+
+#[test]
+fn every_run_reads_from_the_first_data() {
+    let exprs = parse("DATA 1, 2\nREAD a, b\n");
+    let mut interpreter = Interpreter::new(&exprs);
+
+    for _ in 0..2 {
+        assert_eq!(interpreter.interpret(), Ok(Completion::Finished));
+        assert_eq!((integer(&interpreter, "a"), integer(&interpreter, "b")), (1, 2));
+    }
+}
+
+#[test]
+fn restore_rewinds_to_a_line() {
+    let exprs = parse("DATA 1\nDATA 2, 3\nREAD a, b\nRESTORE 2\nREAD c\n");
+    let mut interpreter = Interpreter::new(&exprs);
+
+    assert_eq!(interpreter.interpret(), Ok(Completion::Finished));
+    assert_eq!([integer(&interpreter, "a"), integer(&interpreter, "b"), integer(&interpreter, "c")], [1, 2, 2]);
+}
+
+#[test]
+fn reading_past_the_last_data_is_an_error() {
+    let exprs = parse("DATA 1\nREAD a, b\n");
+    let mut interpreter = Interpreter::new(&exprs);
+
+    assert_eq!(interpreter.interpret(), Err(RuntimeError { kind: ErrorKind::OutOfData, line: 2 }));
+}
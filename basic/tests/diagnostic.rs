@@ -0,0 +1,39 @@
+use basic::{
+    diagnostic::{self, Format},
+    error::{ErrorKind, RuntimeError},
+};
+use serde_json::{json, Value as Json};
+
+const SOURCE: &str = "LET a = 0\nPRINT 1 / a\n";
+
+#[test]
+fn runtime_errors_name_the_file_and_line() {
+    let error = RuntimeError { kind: ErrorKind::DivisionByZero, line: 2 };
+
+    let rendered = diagnostic::render(&error, "zero.bas", SOURCE, Format::Plain);
+    assert_eq!(rendered, "error: Division by zero\n --> zero.bas:2\n  |\n2 | PRINT 1 / a");
+}
+
+#[test]
+fn errors_past_the_end_of_the_source_only_name_the_line() {
+    let error = RuntimeError { kind: ErrorKind::DivisionByZero, line: 7 };
+
+    let rendered = diagnostic::render(&error, "zero.bas", SOURCE, Format::Plain);
+    assert_eq!(rendered, "error: Division by zero\n --> zero.bas:7");
+}
+
+#[test]
+fn runtime_errors_render_as_json() {
+    let error = RuntimeError { kind: ErrorKind::DivisionByZero, line: 2 };
+
+    let rendered: Json = serde_json::from_str(&diagnostic::render(&error, "zero.bas", SOURCE, Format::Json)).unwrap();
+    assert_eq!(rendered, json!({
+        "severity": "error",
+        "file": "zero.bas",
+        "line": 2,
+        "column": 0,
+        "end_column": 0,
+        "message": "Division by zero",
+        "help": null,
+    }));
+}
@@ -0,0 +1,70 @@
+use basic::{
+    dialect::Dialect,
+    error::{ErrorKind, RuntimeError},
+    interpreter::{Completion, Interpreter, Value},
+    parser::{self, Expr},
+};
+
+fn parse(source: &str) -> Vec<Expr> {
+    let (exprs, errors) = parser::parse_program(source, Dialect::default());
+    assert!(errors.is_empty(), "syntax errors: {:?}", errors);
+    exprs
+}
+
+const HANDLED: &str = "ON ERROR GOTO 4\nLET a = 1 / 0\nEND\nLET handled = ERR\nRESUME NEXT\n";
+
+#[test]
+fn on_error_traps_errors_until_resume() {
+    let exprs = parse(HANDLED);
+    let mut interpreter = Interpreter::new(&exprs);
+
+    assert_eq!(interpreter.interpret(), Ok(Completion::Finished));
+    assert!(matches!(interpreter.get_var("handled"), Some(Value::Integer(11))));
+}
+
+#[test]
+fn direct_mode_does_not_use_the_program_handler() {
+    let exprs = parse(HANDLED);
+    let line = parse("LET b = 1 / 0\n");
+    let mut interpreter = Interpreter::new(&exprs);
+
+    assert_eq!(interpreter.interpret(), Ok(Completion::Finished));
+    assert_eq!(
+        interpreter.run_immediate(&line),
+        Err(RuntimeError { kind: ErrorKind::DivisionByZero, line: 1 }),
+    );
+}
+
+#[test]
+fn a_new_run_does_not_use_the_previous_handler() {
+    let exprs = parse("IF first THEN ON ERROR GOTO 4\nLET a = 1 / 0\nEND\nRESUME NEXT\n");
+    let mut interpreter = Interpreter::new(&exprs);
+
+    interpreter.set_var("first", true).unwrap();
+    assert_eq!(interpreter.interpret(), Ok(Completion::Finished));
+
+    interpreter.set_var("first", false).unwrap();
+    assert_eq!(interpreter.interpret(), Err(RuntimeError { kind: ErrorKind::DivisionByZero, line: 2 }));
+}
+
+#[test]
+fn the_handler_is_back_after_cont() {
+    let exprs = parse("ON ERROR GOTO 5\nSTOP\nLET a = 1 / 0\nEND\nLET handled = 1\nRESUME NEXT\n");
+    let line = parse("LET b = 1\n");
+    let mut interpreter = Interpreter::new(&exprs);
+
+    assert_eq!(interpreter.interpret(), Ok(Completion::Break(2)));
+    assert_eq!(interpreter.run_immediate(&line), Ok(Completion::Finished));
+    assert_eq!(interpreter.cont(), Ok(Completion::Finished));
+    assert!(matches!(interpreter.get_var("handled"), Some(Value::Integer(1))));
+}
+
+#[test]
+fn error_raises_codes_and_erl_reports_the_line() {
+    let exprs = parse("ON ERROR GOTO 4\nERROR 8\nEND\nLET code = ERR\nLET at = ERL\nRESUME NEXT\n");
+    let mut interpreter = Interpreter::new(&exprs);
+
+    assert_eq!(interpreter.interpret(), Ok(Completion::Finished));
+    assert!(matches!(interpreter.get_var("code"), Some(Value::Integer(8))));
+    assert!(matches!(interpreter.get_var("at"), Some(Value::Integer(2))));
+}
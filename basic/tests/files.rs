@@ -0,0 +1,272 @@
+use basic::{
+    dialect::Dialect,
+    error::{ErrorKind, RuntimeError},
+    fs::MemoryFileSystem,
+    interpreter::{Interpreter, StepResult},
+    parser,
+};
+
+/// Runs a program against `fs` and returns what it printed. Lines are
+/// counted from the first non-blank one.
+fn run(source: &str, fs: &MemoryFileSystem) -> Result<String, RuntimeError> {
+    let (exprs, errors) = parser::parse_program(source.trim_start(), Dialect::default());
+    assert!(errors.is_empty(), "syntax errors: {:?}", errors);
+
+    let mut interpreter = Interpreter::new(&exprs);
+    interpreter.set_file_system(fs.clone());
+    interpreter.begin();
+
+    let mut output = String::new();
+    loop {
+        match interpreter.step()? {
+            StepResult::Output(text) => output.push_str(&text),
+            StepResult::Finished => return Ok(output),
+            StepResult::Running => {}
+            result => panic!("unexpected step result {:?}", result),
+        }
+    }
+}
+
+#[test]
+fn print_and_input_round_trip() {
+    let fs = MemoryFileSystem::new();
+    let output = run(r#"
+OPEN "data.txt" FOR OUTPUT AS #1
+PRINT #1, "alpha"
+PRINT #1, 42
+CLOSE #1
+OPEN "data.txt" FOR INPUT AS #1
+INPUT #1, a$, n
+CLOSE
+PRINT a$
+PRINT n + 1
+"#, &fs).unwrap();
+
+    assert_eq!(fs.contents("data.txt").unwrap(), b"alpha\n42\n");
+    assert_eq!(output, "alpha\n43\n");
+}
+
+#[test]
+fn input_splits_fields_on_commas_and_keeps_quoted_commas() {
+    let fs = MemoryFileSystem::new();
+    fs.insert("data.txt", "\"Smith, J\", 7 , x\n");
+
+    let output = run(r#"
+OPEN "data.txt" FOR INPUT AS #1
+INPUT #1, name$, age, code$
+PRINT name$
+PRINT age
+PRINT code$
+"#, &fs).unwrap();
+
+    assert_eq!(output, "Smith, J\n7\nx\n");
+}
+
+#[test]
+fn line_input_reads_whole_lines() {
+    let fs = MemoryFileSystem::new();
+    fs.insert("notes.txt", "one, two\r\nthree\n");
+
+    let output = run(r#"
+OPEN "notes.txt" FOR INPUT AS #1
+LINE INPUT #1, first$
+LINE INPUT #1, second$
+PRINT first$
+PRINT second$
+"#, &fs).unwrap();
+
+    assert_eq!(output, "one, two\nthree\n");
+}
+
+#[test]
+fn eof_turns_true_after_the_last_field() {
+    let fs = MemoryFileSystem::new();
+    fs.insert("numbers.txt", "1,2\n");
+
+    let output = run(r#"
+OPEN "numbers.txt" FOR INPUT AS #1
+PRINT EOF(1)
+INPUT #1, a
+PRINT EOF(1)
+INPUT #1, b
+PRINT EOF(1)
+"#, &fs).unwrap();
+
+    assert_eq!(output, "false\nfalse\ntrue\n");
+}
+
+#[test]
+fn append_adds_to_the_end() {
+    let fs = MemoryFileSystem::new();
+    fs.insert("log.txt", "first\n");
+
+    run(r#"
+OPEN "log.txt" FOR APPEND AS #1
+PRINT #1, "second"
+"#, &fs).unwrap();
+
+    assert_eq!(fs.contents("log.txt").unwrap(), b"first\nsecond\n");
+}
+
+#[test]
+fn reading_past_the_end_is_an_error() {
+    let fs = MemoryFileSystem::new();
+    fs.insert("empty.txt", "");
+
+    let error = run(r#"
+OPEN "empty.txt" FOR INPUT AS #1
+LINE INPUT #1, l$
+"#, &fs).unwrap_err();
+
+    assert_eq!(error, RuntimeError { kind: ErrorKind::InputPastEnd, line: 2 });
+}
+
+#[test]
+fn file_errors_can_be_trapped() {
+    let fs = MemoryFileSystem::new();
+
+    let output = run(r#"
+ON ERROR GOTO 5
+OPEN "missing.txt" FOR INPUT AS #1
+PRINT "resumed"
+END
+PRINT ERR
+RESUME NEXT
+"#, &fs).unwrap();
+
+    assert_eq!(output, "53\nresumed\n");
+}
+
+#[test]
+fn writing_to_an_input_file_is_a_bad_file_mode() {
+    let fs = MemoryFileSystem::new();
+    fs.insert("data.txt", "1\n");
+
+    let error = run(r#"
+OPEN "data.txt" FOR INPUT AS #1
+PRINT #1, 2
+"#, &fs).unwrap_err();
+
+    assert_eq!(error.kind, ErrorKind::BadFileMode);
+}
+
+#[test]
+fn field_put_and_get_round_trip_records() {
+    let fs = MemoryFileSystem::new();
+    let output = run(r#"
+OPEN "people.dat" FOR RANDOM AS #1 LEN = 10
+FIELD #1, 4 AS name$, 6 AS city$
+LSET name$ = "Ann"
+RSET city$ = "Oslo"
+PUT #1, 2
+LSET name$ = "Bo"
+LSET city$ = "Rome"
+PUT #1
+LSET name$ = ""
+GET #1, 2
+PRINT name$
+PRINT city$
+GET #1
+PRINT city$
+"#, &fs).unwrap();
+
+    let mut expected = vec![0; 10];
+    expected.extend(b"Ann   OsloBo  Rome  ");
+    assert_eq!(fs.contents("people.dat").unwrap(), expected);
+    assert_eq!(output, "Ann \n  Oslo\nRome  \n");
+}
+
+#[test]
+fn lset_cuts_strings_to_the_field_width() {
+    let fs = MemoryFileSystem::new();
+    let output = run(r#"
+OPEN "r.dat" FOR RANDOM AS #1 LEN = 3
+FIELD #1, 3 AS code$
+LSET code$ = "ABCDEF"
+PRINT code$
+"#, &fs).unwrap();
+
+    assert_eq!(output, "ABC\n");
+}
+
+#[test]
+fn fields_wider_than_the_record_overflow() {
+    let fs = MemoryFileSystem::new();
+    let error = run(r#"
+OPEN "r.dat" FOR RANDOM AS #1 LEN = 4
+FIELD #1, 3 AS a$, 2 AS b$
+"#, &fs).unwrap_err();
+
+    assert_eq!(error.kind, ErrorKind::FieldOverflow);
+}
+
+#[test]
+fn mk_and_cv_functions_round_trip() {
+    let output = run(r#"
+PRINT CVI(MKI$(-2))
+PRINT CVL(MKL$(70000))
+PRINT CVS(MKS$(1.5))
+PRINT CVD(MKD$(0.1))
+PRINT LEN(MKI$(1))
+PRINT LEN(MKD$(1))
+"#, &MemoryFileSystem::new()).unwrap();
+
+    assert_eq!(output, "-2\n70000\n1.5\n0.1\n2\n8\n");
+}
+
+#[test]
+fn binary_files_put_and_get_variables_at_byte_positions() {
+    let fs = MemoryFileSystem::new();
+    let output = run(r#"
+OPEN "values.bin" FOR BINARY AS #1
+LET n% = 258
+LET d# = 2.5
+PUT #1, 1, n%
+PUT #1, , d#
+GET #1, 1, m%
+GET #1, 3, e#
+PRINT m%
+PRINT e#
+"#, &fs).unwrap();
+
+    let mut expected = vec![2, 1];
+    expected.extend(2.5f64.to_le_bytes());
+    assert_eq!(fs.contents("values.bin").unwrap(), expected);
+    assert_eq!(output, "258\n2.5\n");
+}
+
+#[test]
+fn random_files_put_and_get_typed_variables() {
+    let fs = MemoryFileSystem::new();
+    let output = run(r#"
+OPEN "r.dat" FOR RANDOM AS #1 LEN = 4
+LET total& = -5
+PUT #1, 3, total&
+GET #1, 3, back&
+PRINT back&
+"#, &fs).unwrap();
+
+    assert_eq!(fs.contents("r.dat").unwrap()[8..], (-5i32).to_le_bytes());
+    assert_eq!(output, "-5\n");
+}
+
+#[test]
+fn out_of_range_record_numbers_are_errors() {
+    for statement in ["GET #1, 0", "GET #1, 9223372036854775807", "PUT #1, 9223372036854775807"] {
+        let source = format!("OPEN \"r.dat\" FOR RANDOM AS #1\n{}\n", statement);
+        let error = run(&source, &MemoryFileSystem::new()).unwrap_err();
+
+        assert_eq!(error, RuntimeError { kind: ErrorKind::BadRecordNumber, line: 2 }, "{}", statement);
+    }
+}
+
+#[test]
+fn get_and_put_need_a_random_or_binary_file() {
+    let fs = MemoryFileSystem::new();
+    let error = run(r#"
+OPEN "out.txt" FOR OUTPUT AS #1
+PUT #1, 1
+"#, &fs).unwrap_err();
+
+    assert_eq!(error.kind, ErrorKind::BadFileMode);
+}
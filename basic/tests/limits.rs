@@ -0,0 +1,98 @@
+use std::time::Duration;
+
+use basic::{
+    dialect::Dialect,
+    error::{ErrorKind, RuntimeError},
+    interpreter::{Completion, Interpreter},
+    limits::{Limit, Limits},
+    parser::{self, Expr},
+};
+
+fn parse(source: &str) -> Vec<Expr> {
+    let (exprs, errors) = parser::parse_program(source, Dialect::default());
+    assert!(errors.is_empty(), "syntax errors: {:?}", errors);
+    exprs
+}
+
+fn statement_limit(max: u64) -> Limits {
+    Limits { statements: Some(max), ..Limits::default() }
+}
+
+#[test]
+fn statement_limit_counts_only_statements() {
+    // The loop runs 10 times with two statements each, plus the FOR itself.
+    let exprs = parse("FOR i = 1 TO 10\nLET a = i\nLET b = i\nNEXT\n");
+
+    let mut interpreter = Interpreter::new(&exprs);
+    interpreter.set_limits(statement_limit(21));
+    assert_eq!(interpreter.interpret(), Ok(Completion::Finished));
+
+    interpreter.set_limits(statement_limit(20));
+    assert_eq!(
+        interpreter.interpret(),
+        Err(RuntimeError { kind: ErrorKind::LimitExceeded(Limit::Statements), line: 3 }),
+    );
+}
+
+#[test]
+fn statement_limit_carries_over_cont() {
+    let exprs = parse("LET a = 1\nSTOP\nLET b = 2\nLET c = 3\n");
+
+    let mut interpreter = Interpreter::new(&exprs);
+    interpreter.set_limits(statement_limit(3));
+    assert_eq!(interpreter.interpret(), Ok(Completion::Break(2)));
+    assert_eq!(
+        interpreter.cont(),
+        Err(RuntimeError { kind: ErrorKind::LimitExceeded(Limit::Statements), line: 4 }),
+    );
+}
+
+fn limit_error(limit: Limit, line: usize) -> Result<Completion, RuntimeError> {
+    Err(RuntimeError { kind: ErrorKind::LimitExceeded(limit), line })
+}
+
+#[test]
+fn time_limit_stops_endless_loops() {
+    let exprs = parse("LET a = 0\nFOR i = 1 TO 1000000000\nLET a = a + 1\nNEXT\n");
+
+    let mut interpreter = Interpreter::new(&exprs);
+    interpreter.set_limits(Limits { time: Some(Duration::from_millis(20)), ..Limits::default() });
+    assert!(matches!(
+        interpreter.interpret(),
+        Err(RuntimeError { kind: ErrorKind::LimitExceeded(Limit::Time), .. }),
+    ));
+}
+
+#[test]
+fn memory_limit_counts_the_strings_held_in_variables() {
+    let exprs = parse("LET a$ = \"abcd\"\nLET a$ = \"efgh\"\nLET b$ = \"i\"\n");
+
+    let mut interpreter = Interpreter::new(&exprs);
+    interpreter.set_limits(Limits { memory: Some(5), ..Limits::default() });
+    assert_eq!(interpreter.interpret(), Ok(Completion::Finished));
+
+    let mut interpreter = Interpreter::new(&exprs);
+    interpreter.set_limits(Limits { memory: Some(4), ..Limits::default() });
+    assert_eq!(interpreter.interpret(), limit_error(Limit::Memory, 3));
+}
+
+#[test]
+fn depth_limit_counts_nested_blocks() {
+    let exprs = parse("FOR i = 1 TO 2\nFOR j = 1 TO 2\nIF i = j THEN LET a = 1\nNEXT\nNEXT\n");
+
+    let mut interpreter = Interpreter::new(&exprs);
+    interpreter.set_limits(Limits { depth: Some(3), ..Limits::default() });
+    assert_eq!(interpreter.interpret(), Ok(Completion::Finished));
+
+    interpreter.set_limits(Limits { depth: Some(2), ..Limits::default() });
+    assert_eq!(interpreter.interpret(), limit_error(Limit::Depth, 3));
+}
+
+#[test]
+fn limits_cannot_be_trapped() {
+    let exprs = parse("ON ERROR GOTO 3\nLET a$ = \"abcdef\"\nRESUME NEXT\n");
+
+    let mut interpreter = Interpreter::new(&exprs);
+    interpreter.set_limits(Limits { memory: Some(4), ..Limits::default() });
+    assert_eq!(interpreter.interpret(), limit_error(Limit::Memory, 2));
+}
@@ -0,0 +1,93 @@
+use std::io::Cursor;
+
+use basic::{dialect::Dialect, lsp};
+use serde_json::{json, Value as Json};
+
+const URI: &str = "file:///test.bas";
+
+/// Opens `text` as a document, sends `requests` about it and returns
+/// everything the server sent back.
+fn session(text: &str, requests: &[(&str, Json)]) -> Vec<Json> {
+    let mut messages = vec![
+        json!({ "jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {} }),
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": { "textDocument": { "uri": URI, "languageId": "basic", "version": 1, "text": text } },
+        }),
+    ];
+    for (id, (method, params)) in requests.iter().enumerate() {
+        let mut params = params.clone();
+        params["textDocument"] = json!({ "uri": URI });
+        messages.push(json!({ "jsonrpc": "2.0", "id": id + 1, "method": method, "params": params }));
+    }
+    messages.push(json!({ "jsonrpc": "2.0", "method": "exit" }));
+
+    let mut input = Vec::new();
+    for message in messages {
+        let body = message.to_string();
+        input.extend(format!("Content-Length: {}\r\n\r\n{}", body.len(), body).into_bytes());
+    }
+
+    let mut output = Vec::new();
+    lsp::serve(Cursor::new(input), &mut output, Dialect::default()).expect("error serving session");
+
+    let mut replies = Vec::new();
+    let mut rest = std::str::from_utf8(&output).expect("output is not UTF-8");
+    while let Some((header, body)) = rest.split_once("\r\n\r\n") {
+        let length: usize = header.trim_start_matches("Content-Length: ").parse().expect("bad Content-Length");
+        replies.push(serde_json::from_str(&body[..length]).expect("message is not JSON"));
+        rest = &body[length..];
+    }
+    replies
+}
+
+fn result(replies: &[Json], id: usize) -> &Json {
+    &replies.iter().find(|reply| reply["id"] == id).expect("no reply")["result"]
+}
+
+#[test]
+fn diagnostics_carry_help() {
+    let replies = session("LET a = 1\nIF a THN PRINT a\n", &[]);
+
+    let diagnostics = &replies.iter()
+        .find(|reply| reply["method"] == "textDocument/publishDiagnostics")
+        .expect("no diagnostics")["params"]["diagnostics"];
+    assert_eq!(diagnostics, &json!([{
+        "range": { "start": { "line": 1, "character": 5 }, "end": { "line": 1, "character": 8 } },
+        "severity": 1,
+        "source": "basic",
+        "message": "Expected `THEN`, got `THN`\nhelp: did you mean `THEN`?",
+    }]));
+}
+
+#[test]
+fn symbols_skip_misspelled_keywords() {
+    let replies = session("LET a = 1\nIF a THN PRINT a\nFOR i = 1 TO 3\nNEXT\n", &[
+        ("textDocument/documentSymbol", json!({})),
+    ]);
+
+    let names: Vec<_> = result(&replies, 1).as_array().unwrap().iter().map(|symbol| symbol["name"].clone()).collect();
+    assert_eq!(names, [json!("a"), json!("i")]);
+}
+
+#[test]
+fn definitions_of_variables_and_line_numbers() {
+    let replies = session("LET total = 0\nON ERROR GOTO 4\nPRINT total\nRESUME NEXT\n", &[
+        ("textDocument/definition", json!({ "position": { "line": 2, "character": 8 } })),
+        ("textDocument/definition", json!({ "position": { "line": 1, "character": 14 } })),
+    ]);
+
+    assert_eq!(result(&replies, 1)["range"]["start"], json!({ "line": 0, "character": 4 }));
+    assert_eq!(result(&replies, 2)["range"]["start"], json!({ "line": 3, "character": 0 }));
+}
+
+#[test]
+fn hover_shows_built_in_signatures() {
+    let replies = session("PRINT LEN(\"abc\")\n", &[
+        ("textDocument/hover", json!({ "position": { "line": 0, "character": 7 } })),
+    ]);
+
+    let hover = result(&replies, 1)["contents"]["value"].as_str().unwrap();
+    assert!(hover.starts_with("```basic\nLEN(s$)\n```"), "{}", hover);
+}
@@ -0,0 +1,112 @@
+use std::{cell::RefCell, rc::Rc};
+
+use basic::{
+    dialect::Dialect,
+    error::{ErrorKind, RuntimeError},
+    interpreter::{Completion, Interpreter, Value, VarType},
+    parser::{self, Expr},
+};
+
+fn parse(source: &str) -> Vec<Expr> {
+    let (exprs, errors) = parser::parse_program(source, Dialect::default());
+    assert!(errors.is_empty(), "syntax errors: {:?}", errors);
+    exprs
+}
+
+fn error(kind: ErrorKind) -> Result<Completion, RuntimeError> {
+    Err(RuntimeError { kind, line: 1 })
+}
+
+/// An interpreter for `exprs` with `ADD(a%, b%)` and `GREET$(name$)`.
+fn interpreter(exprs: &[Expr]) -> Interpreter<'_> {
+    let mut interpreter = Interpreter::new(exprs);
+    interpreter.register_fn("add", &[VarType::Integer, VarType::Integer], |args| {
+        let (a, b) = (i64::try_from(args[0].clone())?, i64::try_from(args[1].clone())?);
+        Ok(Value::from(a + b))
+    });
+    interpreter.register_fn("Greet$", &[VarType::String], |args| {
+        Ok(Value::from(format!("hello, {}", String::try_from(args[0].clone())?)))
+    });
+    interpreter
+}
+
+#[test]
+fn native_functions_are_called_case_insensitively() {
+    let exprs = parse("LET a = Add(2, 3) * ADD(1, 1)\nLET g$ = greet$(\"ann\")\n");
+    let mut interpreter = interpreter(&exprs);
+
+    assert_eq!(interpreter.interpret(), Ok(Completion::Finished));
+    assert!(matches!(interpreter.get_var("a"), Some(Value::Integer(10))));
+    assert!(matches!(interpreter.get_var("g$"), Some(Value::String(s)) if s == "hello, ann"));
+}
+
+#[test]
+fn arguments_are_coerced_to_their_parameter_types() {
+    let exprs = parse("LET a = ADD(1.6, 2.2)\n");
+    let mut interpreter = interpreter(&exprs);
+
+    assert_eq!(interpreter.interpret(), Ok(Completion::Finished));
+    assert!(matches!(interpreter.get_var("a"), Some(Value::Integer(4))));
+}
+
+#[test]
+fn arguments_out_of_range_overflow() {
+    let exprs = parse("LET a = ADD(40000, 1)\n");
+    assert_eq!(interpreter(&exprs).interpret(), error(ErrorKind::Overflow));
+}
+
+#[test]
+fn arguments_of_the_wrong_type_are_mismatches() {
+    let exprs = parse("LET a = ADD(1, \"two\")\n");
+    assert_eq!(interpreter(&exprs).interpret(), error(ErrorKind::TypeMismatch("argument 2 of ADD".to_string())));
+
+    let exprs = parse("LET g$ = GREET$(5)\n");
+    assert_eq!(interpreter(&exprs).interpret(), error(ErrorKind::TypeMismatch("argument 1 of GREET$".to_string())));
+}
+
+#[test]
+fn calls_with_the_wrong_number_of_arguments_are_illegal() {
+    let wrong = |name: &str| error(ErrorKind::IllegalFunctionCall(format!("wrong number of arguments to {}", name)));
+
+    let exprs = parse("LET a = ADD(1)\n");
+    assert_eq!(interpreter(&exprs).interpret(), wrong("ADD"));
+    let exprs = parse("LET a = ADD(1, 2, 3)\n");
+    assert_eq!(interpreter(&exprs).interpret(), wrong("ADD"));
+    let exprs = parse("LET g$ = GREET$()\n");
+    assert_eq!(interpreter(&exprs).interpret(), wrong("GREET$"));
+}
+
+#[test]
+fn unknown_functions_are_illegal() {
+    let exprs = parse("LET a = MISSING(1)\n");
+    assert_eq!(
+        interpreter(&exprs).interpret(),
+        error(ErrorKind::IllegalFunctionCall("undefined function MISSING".to_string())),
+    );
+}
+
+#[test]
+fn errors_from_native_functions_can_be_trapped() {
+    let exprs = parse("ON ERROR GOTO 4\nLET a = FAIL(1)\nEND\nLET code = ERR\nRESUME NEXT\n");
+    let mut interpreter = Interpreter::new(&exprs);
+    interpreter.register_fn("FAIL", &[VarType::Integer], |_| Err(ErrorKind::DivisionByZero));
+
+    assert_eq!(interpreter.interpret(), Ok(Completion::Finished));
+    assert!(matches!(interpreter.get_var("code"), Some(Value::Integer(11))));
+}
+
+#[test]
+fn built_ins_take_precedence() {
+    let calls = Rc::new(RefCell::new(0));
+    let exprs = parse("LET a = LEN(\"abc\")\n");
+    let mut interpreter = Interpreter::new(&exprs);
+    let counter = calls.clone();
+    interpreter.register_fn("LEN", &[VarType::String], move |_| {
+        *counter.borrow_mut() += 1;
+        Ok(Value::from(0))
+    });
+
+    assert_eq!(interpreter.interpret(), Ok(Completion::Finished));
+    assert!(matches!(interpreter.get_var("a"), Some(Value::Integer(3))));
+    assert_eq!(*calls.borrow(), 0);
+}
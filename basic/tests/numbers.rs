@@ -0,0 +1,108 @@
+use basic::{
+    dialect::{Dialect, Overflow},
+    error::{ErrorKind, RuntimeError},
+    interpreter::{Interpreter, StepResult},
+    parser,
+};
+
+/// Runs a program in `dialect` and returns what it printed.
+fn run_with(source: &str, dialect: Dialect) -> Result<String, RuntimeError> {
+    let (exprs, errors) = parser::parse_program(source, dialect);
+    assert!(errors.is_empty(), "syntax errors: {:?}", errors);
+
+    let mut interpreter = Interpreter::with_dialect(&exprs, dialect);
+    interpreter.begin();
+
+    let mut output = String::new();
+    loop {
+        match interpreter.step()? {
+            StepResult::Output(text) => output.push_str(&text),
+            StepResult::Finished => return Ok(output),
+            StepResult::Running => {}
+            result => panic!("unexpected step result {:?}", result),
+        }
+    }
+}
+
+fn run(source: &str) -> Result<String, RuntimeError> {
+    run_with(source, Dialect::default())
+}
+
+#[test]
+fn exponent_literals_print_as_single_precision() {
+    assert_eq!(run("PRINT 1.5E-3\nPRINT 2.5e2\nPRINT 0.1!\n").unwrap(), "0.0015\n250\n0.1\n");
+}
+
+#[test]
+fn d_exponents_and_hash_suffixes_keep_double_precision() {
+    assert_eq!(run("PRINT 1.1D0\nPRINT 0.1#\n").unwrap(), "1.1\n0.1\n");
+    assert_eq!(run("PRINT 1.1D0 * 3\n").unwrap(), "3.3000000000000003\n");
+}
+
+#[test]
+fn single_arithmetic_stays_single() {
+    assert_eq!(run("PRINT 1.1E0 * 2\nPRINT 1.1E0 / 2E0\nPRINT -1.5E-3\n").unwrap(), "2.2\n0.55\n-0.0015\n");
+}
+
+#[test]
+fn single_variables_print_as_single_precision() {
+    let source = "LET x! = 0.1\nPRINT x!\nLET s! = 1.1\nPRINT s! * 2\nPRINT CVS(MKS$(0.1))\n";
+    assert_eq!(run(source).unwrap(), "0.1\n2.2\n0.1\n");
+}
+
+#[test]
+fn double_variables_widen_singles() {
+    assert_eq!(run("LET d# = 0.5E0\nPRINT d# / 3\n").unwrap(), "0.16666666666666666\n");
+}
+
+#[test]
+fn literals_may_start_with_a_decimal_point() {
+    assert_eq!(run("PRINT .5\nPRINT -.25 + 1\n").unwrap(), "0.5\n0.75\n");
+}
+
+#[test]
+fn literals_out_of_range_are_syntax_errors() {
+    for literal in ["1E400", "1E39", "1D400", "3.5E38!"] {
+        let (_, errors) = parser::parse_program(&format!("PRINT {}\n", literal), Dialect::default());
+        let messages: Vec<_> = errors.iter().map(|error| error.message.clone()).collect();
+        assert_eq!(messages, [format!("Overflow in literal {}", literal)]);
+    }
+}
+
+const MAX: &str = "LET n = 9223372036854775807\n";
+const MIN: &str = "LET n = -9223372036854775807 - 1\n";
+
+fn promote() -> Dialect {
+    Dialect { overflow: Overflow::Promote, ..Dialect::default() }
+}
+
+#[test]
+fn integer_overflow_is_an_error() {
+    let overflow = Err(RuntimeError { kind: ErrorKind::Overflow, line: 2 });
+    assert_eq!(run(&format!("{}PRINT n + 1\n", MAX)), overflow);
+    assert_eq!(run(&format!("{}PRINT n * 2\n", MAX)), overflow);
+    assert_eq!(run(&format!("{}PRINT -n\n", MIN)), overflow);
+    assert_eq!(run(&format!("{}PRINT n - 1\n", MIN)), overflow);
+}
+
+#[test]
+fn integer_overflow_promotes_to_floating_point() {
+    assert_eq!(run_with(&format!("{}PRINT n + 1\n", MAX), promote()).unwrap(), "9223372036854776000\n");
+    assert_eq!(run_with(&format!("{}PRINT -n\n", MIN), promote()).unwrap(), "9223372036854776000\n");
+    assert_eq!(run_with(&format!("{}PRINT n + 1\n", "LET n = 1\n"), promote()).unwrap(), "2\n");
+}
+
+#[test]
+fn division_and_modulo_by_zero_are_errors() {
+    for dialect in [Dialect::default(), promote()] {
+        for source in ["PRINT 5 % 0\n", "PRINT 5 / 0\n", "PRINT 5.5 % 0\n", "PRINT 1E0 / 0\n"] {
+            assert_eq!(run_with(source, dialect), Err(RuntimeError { kind: ErrorKind::DivisionByZero, line: 1 }), "{}", source);
+        }
+    }
+}
+
+#[test]
+fn modulo_of_the_smallest_integer_by_minus_one_overflows() {
+    assert_eq!(run(&format!("{}PRINT n % -1\n", MIN)), Err(RuntimeError { kind: ErrorKind::Overflow, line: 2 }));
+    assert_eq!(run(&format!("{}PRINT n / -1\n", MIN)), Err(RuntimeError { kind: ErrorKind::Overflow, line: 2 }));
+}
@@ -0,0 +1,73 @@
+use basic::{
+    dialect::Dialect,
+    interpreter::{Interpreter, StepResult},
+    parser::{self, Expr},
+};
+
+fn parse(source: &str) -> Vec<Expr> {
+    let (exprs, errors) = parser::parse_program(source, Dialect::default());
+    assert!(errors.is_empty(), "syntax errors: {:?}", errors);
+    exprs
+}
+
+/// Runs the interpreter's program from the start and returns what it
+/// printed.
+fn run(interpreter: &mut Interpreter) -> String {
+    interpreter.begin();
+
+    let mut output = String::new();
+    loop {
+        match interpreter.step().expect("program failed") {
+            StepResult::Output(text) => output.push_str(&text),
+            StepResult::Finished => return output,
+            StepResult::Running => {}
+            result => panic!("unexpected step result {:?}", result),
+        }
+    }
+}
+
+fn first_values(source: &str) -> String {
+    let exprs = parse(source);
+    run(&mut Interpreter::new(&exprs))
+}
+
+const THREE: &str = "PRINT RND\nPRINT RND\nPRINT RND\n";
+
+#[test]
+fn a_fixed_seed_gives_a_fixed_sequence() {
+    let exprs = parse(THREE);
+    let mut interpreter = Interpreter::new(&exprs);
+    interpreter.set_seed(7);
+
+    let expected = "0.3898297483912715\n0.01678829452815611\n0.9007606806068834\n";
+    assert_eq!(run(&mut interpreter), expected);
+    assert_eq!(run(&mut interpreter), expected);
+}
+
+#[test]
+fn the_default_seed_repeats_on_every_run() {
+    let exprs = parse(THREE);
+    let mut interpreter = Interpreter::new(&exprs);
+
+    let expected = "0.038848734697185194\n0.3328011087394298\n0.3646818563781382\n";
+    assert_eq!(run(&mut interpreter), expected);
+    assert_eq!(run(&mut interpreter), expected);
+}
+
+#[test]
+fn equal_seeds_agree_whatever_their_type() {
+    let randomize = first_values(&format!("RANDOMIZE 42\n{}", THREE));
+    assert_eq!(first_values(&format!("RANDOMIZE 42.0\n{}", THREE)), randomize);
+    assert_eq!(first_values(&format!("LET s# = 42\nRANDOMIZE s#\n{}", THREE)), randomize);
+
+    assert_eq!(
+        first_values("RANDOMIZE -1\nPRINT RND\nPRINT RND\n"),
+        first_values("PRINT RND(-1)\nPRINT RND\n"),
+    );
+}
+
+#[test]
+fn rnd_zero_repeats_the_last_value() {
+    let output = first_values("LET a = RND\nLET b = RND(0)\nPRINT a = b\nPRINT RND(0) = RND\n");
+    assert_eq!(output, "true\nfalse\n");
+}
@@ -0,0 +1,56 @@
+use basic::{
+    dialect::Dialect,
+    error::{ErrorKind, RuntimeError},
+    interpreter::{Completion, Interpreter, Value},
+    parser,
+};
+
+const BROKEN: &str = "IF a THN PRINT 1\nLET = 2\nLET b = 3\nPRINT 4 +\n";
+
+#[test]
+fn every_broken_statement_is_reported_in_source_order() {
+    let (_, errors) = parser::parse_program(BROKEN, Dialect::default());
+
+    let found: Vec<_> = errors.iter().map(|error| (error.span.line, error.span.col, error.message.as_str())).collect();
+    assert_eq!(found, [
+        (1, 6, "Expected `THEN`, got `THN`"),
+        (2, 5, "Expected a variable, got `=`"),
+        (4, 10, "Expected an expression, got end of line"),
+    ]);
+    assert_eq!(errors[0].help.as_deref(), Some("did you mean `THEN`?"));
+}
+
+#[test]
+fn lexer_and_parser_errors_are_merged() {
+    let (_, errors) = parser::parse_program("PRINT \"open\nLET = 1\nPRINT &H\n", Dialect::default());
+
+    let lines: Vec<_> = errors.iter().map(|error| (error.span.line, error.message.as_str())).collect();
+    assert_eq!(lines, [
+        (1, "Unterminated string literal"),
+        (2, "Expected a variable, got `=`"),
+        (3, "Expected digits in literal"),
+    ]);
+}
+
+#[test]
+fn running_a_broken_statement_is_a_syntax_error() {
+    let (exprs, _) = parser::parse_program("LET a = 1\nLET = 2\nLET b = 3\n", Dialect::default());
+    let mut interpreter = Interpreter::new(&exprs);
+
+    assert_eq!(
+        interpreter.interpret(),
+        Err(RuntimeError { kind: ErrorKind::Syntax("Expected a variable, got `=`".to_string()), line: 2 }),
+    );
+    assert!(matches!(interpreter.get_var("a"), Some(Value::Integer(1))));
+    assert!(interpreter.get_var("b").is_none());
+}
+
+#[test]
+fn syntax_errors_at_runtime_can_be_trapped() {
+    let source = "ON ERROR GOTO 4\nLET = 2\nEND\nLET code = ERR\nRESUME NEXT\n";
+    let (exprs, _) = parser::parse_program(source, Dialect::default());
+    let mut interpreter = Interpreter::new(&exprs);
+
+    assert_eq!(interpreter.interpret(), Ok(Completion::Finished));
+    assert!(matches!(interpreter.get_var("code"), Some(Value::Integer(2))));
+}
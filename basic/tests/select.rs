@@ -0,0 +1,72 @@
+use basic::{
+    dialect::Dialect,
+    interpreter::{Interpreter, StepResult, Value},
+    parser,
+};
+
+const GRADES: &str = "\
+SELECT CASE score
+CASE 100
+PRINT \"perfect\"
+CASE 90 TO 99, 85
+PRINT \"great\"
+CASE IS >= 50
+PRINT \"pass\"
+CASE ELSE
+PRINT \"fail\"
+END SELECT
+";
+
+const NAMES: &str = "\
+SELECT CASE name$
+CASE \"ann\", \"bob\"
+PRINT \"friend\"
+CASE \"m\" TO \"p\"
+PRINT \"middle\"
+CASE IS < \"c\"
+PRINT \"early\"
+END SELECT
+PRINT \"done\"
+";
+
+/// Runs `source` with `name` set to `value` and returns what it printed.
+fn select(source: &str, name: &str, value: impl Into<Value>) -> String {
+    let (exprs, errors) = parser::parse_program(source, Dialect::default());
+    assert!(errors.is_empty(), "syntax errors: {:?}", errors);
+
+    let mut interpreter = Interpreter::new(&exprs);
+    interpreter.set_var(name, value).unwrap();
+    interpreter.begin();
+
+    let mut output = String::new();
+    loop {
+        match interpreter.step().expect("program failed") {
+            StepResult::Output(text) => output.push_str(&text),
+            StepResult::Finished => return output,
+            StepResult::Running => {}
+            result => panic!("unexpected step result {:?}", result),
+        }
+    }
+}
+
+#[test]
+fn numbers_match_values_ranges_and_is_tests() {
+    let cases = [(100, "perfect"), (95, "great"), (90, "great"), (85, "great"), (89, "pass"), (50, "pass"), (10, "fail")];
+    for (score, expected) in cases {
+        assert_eq!(select(GRADES, "score", score), format!("{}\n", expected), "score {}", score);
+    }
+}
+
+#[test]
+fn strings_match_values_ranges_and_is_tests() {
+    let cases = [("bob", "friend\n"), ("nina", "middle\n"), ("m", "middle\n"), ("abe", "early\n"), ("zed", "")];
+    for (name, expected) in cases {
+        assert_eq!(select(NAMES, "name$", name), format!("{}done\n", expected), "name {}", name);
+    }
+}
+
+#[test]
+fn only_the_first_matching_case_runs() {
+    let source = "SELECT CASE n\nCASE 1 TO 10\nPRINT \"low\"\nCASE 5\nPRINT \"five\"\nEND SELECT\n";
+    assert_eq!(select(source, "n", 5), "low\n");
+}
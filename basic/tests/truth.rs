@@ -0,0 +1,64 @@
+use basic::{
+    dialect::{Dialect, Truth},
+    error::{ErrorKind, RuntimeError},
+    interpreter::{Interpreter, StepResult},
+    parser,
+};
+
+fn run_with(source: &str, truth: Truth) -> Result<String, RuntimeError> {
+    let dialect = Dialect { truth, ..Dialect::default() };
+    let (exprs, errors) = parser::parse_program(source, dialect);
+    assert!(errors.is_empty(), "syntax errors: {:?}", errors);
+
+    let mut interpreter = Interpreter::with_dialect(&exprs, dialect);
+    interpreter.begin();
+
+    let mut output = String::new();
+    loop {
+        match interpreter.step()? {
+            StepResult::Output(text) => output.push_str(&text),
+            StepResult::Finished => return Ok(output),
+            StepResult::Running => {}
+            result => panic!("unexpected step result {:?}", result),
+        }
+    }
+}
+
+const COMPARISONS: &str = "PRINT 1 = 1\nPRINT 1 > 2\nPRINT \"a\" < \"b\"\nPRINT 2 <> 2\n";
+
+#[test]
+fn classic_comparisons_yield_minus_one_and_zero() {
+    assert_eq!(run_with(COMPARISONS, Truth::Classic).unwrap(), "-1\n0\n-1\n0\n");
+}
+
+#[test]
+fn boolean_comparisons_yield_booleans() {
+    assert_eq!(run_with(COMPARISONS, Truth::Boolean).unwrap(), "true\nfalse\ntrue\nfalse\n");
+}
+
+#[test]
+fn classic_truth_values_are_numbers() {
+    assert_eq!(run_with("LET t = 1 < 2\nPRINT t * 5\nPRINT t + 1\n", Truth::Classic).unwrap(), "-5\n0\n");
+}
+
+#[test]
+fn classic_if_treats_any_non_zero_number_as_true() {
+    let source = "IF 0 THEN PRINT \"zero\"\nIF 7 THEN PRINT \"seven\"\nIF -0.5 THEN PRINT \"half\"\nIF 2 > 1 THEN PRINT \"compared\"\n";
+    assert_eq!(run_with(source, Truth::Classic).unwrap(), "seven\nhalf\ncompared\n");
+}
+
+#[test]
+fn boolean_if_rejects_numbers() {
+    assert_eq!(
+        run_with("IF 1 THEN PRINT \"one\"\n", Truth::Boolean),
+        Err(RuntimeError { kind: ErrorKind::TypeMismatch("IF condition must be a boolean".to_string()), line: 1 }),
+    );
+}
+
+#[test]
+fn classic_if_rejects_strings() {
+    assert_eq!(
+        run_with("IF \"yes\" THEN PRINT \"one\"\n", Truth::Classic),
+        Err(RuntimeError { kind: ErrorKind::TypeMismatch("IF condition must be a number".to_string()), line: 1 }),
+    );
+}
@@ -0,0 +1,83 @@
+use basic::{
+    dialect::Dialect,
+    error::ErrorKind,
+    interpreter::{Completion, Interpreter, Value},
+    parser,
+};
+
+#[test]
+fn variables_round_trip_through_a_script() {
+    let (exprs, errors) = parser::parse_program("LET total = price * count\nLET label$ = name$\n", Dialect::default());
+    assert!(errors.is_empty(), "syntax errors: {:?}", errors);
+
+    let mut interpreter = Interpreter::new(&exprs);
+    interpreter.set_var("price", 7).unwrap();
+    interpreter.set_var("count", 6i64).unwrap();
+    interpreter.set_var("name$", "ann").unwrap();
+    assert_eq!(interpreter.interpret(), Ok(Completion::Finished));
+
+    assert_eq!(i64::try_from(interpreter.get_var("total").unwrap().clone()), Ok(42));
+    assert_eq!(String::try_from(interpreter.get_var("label$").unwrap().clone()), Ok("ann".to_string()));
+    assert!(interpreter.get_var("missing").is_none());
+
+    let mut names: Vec<_> = interpreter.vars().map(|(name, _)| name).collect();
+    names.sort();
+    assert_eq!(names, ["count", "label$", "name$", "price", "total"]);
+}
+
+#[test]
+fn set_var_converts_like_an_assignment() {
+    let mut interpreter = Interpreter::new(&[]);
+
+    interpreter.set_var("n%", 2.6).unwrap();
+    assert!(matches!(interpreter.get_var("n%"), Some(Value::Integer(3))));
+    interpreter.set_var("x!", 0.1).unwrap();
+    assert!(matches!(interpreter.get_var("x!"), Some(Value::Single(x)) if *x == 0.1f32));
+    interpreter.set_var("d#", 1).unwrap();
+    assert!(matches!(interpreter.get_var("d#"), Some(Value::Float(x)) if *x == 1.0));
+
+    assert_eq!(interpreter.set_var("n%", 40000), Err(ErrorKind::Overflow));
+    assert_eq!(interpreter.set_var("l&", 3_000_000_000i64), Err(ErrorKind::Overflow));
+    assert_eq!(interpreter.set_var("s$", 1), Err(ErrorKind::TypeMismatch("variable s$".to_string())));
+    assert_eq!(interpreter.set_var("n%", "one"), Err(ErrorKind::TypeMismatch("variable n%".to_string())));
+}
+
+#[test]
+fn values_convert_from_rust_types() {
+    assert!(matches!(Value::from(5i32), Value::Integer(5)));
+    assert!(matches!(Value::from(5i64), Value::Integer(5)));
+    assert!(matches!(Value::from(0.5f32), Value::Single(x) if x == 0.5));
+    assert!(matches!(Value::from(0.5f64), Value::Float(x) if x == 0.5));
+    assert!(matches!(Value::from("a"), Value::String(s) if s == "a"));
+    assert!(matches!(Value::from("a".to_string()), Value::String(s) if s == "a"));
+    assert!(matches!(Value::from(true), Value::Bool(true)));
+}
+
+#[test]
+fn values_convert_to_rust_types() {
+    assert_eq!(i64::try_from(Value::Float(2.5)), Ok(3));
+    assert_eq!(i64::try_from(Value::Single(-1.4)), Ok(-1));
+    assert_eq!(i32::try_from(Value::Integer(-7)), Ok(-7));
+    assert_eq!(f64::try_from(Value::Integer(2)), Ok(2.0));
+    assert_eq!(f64::try_from(Value::Single(0.5)), Ok(0.5));
+    assert_eq!(bool::try_from(Value::Integer(-1)), Ok(true));
+    assert_eq!(bool::try_from(Value::Float(0.0)), Ok(false));
+}
+
+#[test]
+fn values_of_the_wrong_kind_fail_to_convert() {
+    let number = || ErrorKind::TypeMismatch("expected a number".to_string());
+
+    assert_eq!(i64::try_from(Value::from("1")), Err(number()));
+    assert_eq!(i64::try_from(Value::Bool(true)), Err(number()));
+    assert_eq!(f64::try_from(Value::from("1.5")), Err(number()));
+    assert_eq!(String::try_from(Value::Integer(1)), Err(ErrorKind::TypeMismatch("expected a string".to_string())));
+    assert_eq!(bool::try_from(Value::from("yes")), Err(ErrorKind::TypeMismatch("expected a boolean".to_string())));
+}
+
+#[test]
+fn values_out_of_range_fail_to_convert() {
+    assert_eq!(i32::try_from(Value::Integer(3_000_000_000)), Err(ErrorKind::Overflow));
+    assert_eq!(i64::try_from(Value::Float(1e30)), Err(ErrorKind::Overflow));
+    assert_eq!(i64::try_from(Value::Float(f64::NAN)), Err(ErrorKind::Overflow));
+}